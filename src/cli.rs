@@ -0,0 +1,253 @@
+use simcity::{Area, ColorMode, Layer, OutputMode};
+
+pub const USAGE: &str = "\
+Usage: simcity [CONFIG] [OPTIONS]
+       simcity --resume <SNAPSHOT> [OPTIONS]
+
+Runs the simulation described by CONFIG (a .txt config file), or continues
+the one saved in SNAPSHOT. Without any arguments the simulator asks for the
+config file and analysis area on stdin.
+
+Options:
+  -r, --region <FILE>         Region layout (.csv), overrides 'Region Layout'
+  -t, --time-limit <N>        Overrides 'Time Limit'
+  -f, --refresh-rate <N>      Overrides 'Refresh Rate'
+  -p, --power-capacity <N>    Overrides 'Power Plant Capacity'
+  -i, --interactive           Steps through the simulation in a full-screen view
+  -o, --output <MODE>         'full' (default), 'final' or 'json'
+  -l, --layers <LIST>         Comma separated layers of the region to print:
+                              zoning (default), power, pollution, population,
+                              land-value, roads
+  -c, --color <WHEN>          'auto' (default), 'always' or 'never'
+  -a, --area <X1,Y1,X2,Y2>    Prints the analysis of this area after the run
+  -m, --metrics <FILE>        Writes statistics of every time step as CSV
+      --images <DIR>          Writes SVG images of the initial, refresh and
+                              final states to DIR
+      --frames <DIR>          Writes every refresh state to DIR as numbered
+                              SVG frames and an animation
+      --frame-duration <MS>   Time every frame is shown in the animation
+                              (default 500)
+      --image-pollution       Draws a pollution heatmap on the images and frames
+  -s, --save <FILE>           Saves a snapshot of the simulation after the run
+      --resume <FILE>         Continues the simulation saved in a snapshot
+  -h, --help                  Prints this message
+
+Exit codes:
+  0  The simulation ran to completion
+  1  The config, region layout, snapshot or analysis area is invalid
+  2  The command line arguments are invalid";
+
+#[derive(Debug, Default)]
+pub struct Args {
+    pub config_filename: Option<String>,
+    pub region_layout_filename: Option<String>,
+    pub time_limit: Option<u32>,
+    pub refresh_rate: Option<u32>,
+    pub power_plant_capacity: Option<u32>,
+    pub output_mode: OutputMode,
+    pub color_mode: ColorMode,
+    pub layers: Option<Vec<Layer>>,
+    pub area: Option<Area>,
+    pub metrics_filename: Option<String>,
+    pub images_directory: Option<String>,
+    pub frames_directory: Option<String>,
+    pub frame_duration: Option<u32>,
+    pub image_pollution: bool,
+    pub save_filename: Option<String>,
+    pub resume_filename: Option<String>,
+    pub interactive: bool,
+    pub help: bool,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for '{}'", name))
+            };
+
+            match arg.as_str() {
+                "-h" | "--help" => parsed.help = true,
+                "-i" | "--interactive" => parsed.interactive = true,
+                "-r" | "--region" => parsed.region_layout_filename = Some(value(&arg)?),
+                "-t" | "--time-limit" => {
+                    parsed.time_limit = Some(Self::parse_positive(&arg, &value(&arg)?)?)
+                }
+                "-f" | "--refresh-rate" => {
+                    parsed.refresh_rate = Some(Self::parse_positive(&arg, &value(&arg)?)?)
+                }
+                "-p" | "--power-capacity" => {
+                    parsed.power_plant_capacity = Some(Self::parse_positive(&arg, &value(&arg)?)?)
+                }
+                "-o" | "--output" => {
+                    parsed.output_mode = match value(&arg)?.as_str() {
+                        "full" => OutputMode::Full,
+                        "final" => OutputMode::Final,
+                        "json" => OutputMode::Json,
+                        other => return Err(format!("Unknown output mode '{}'", other)),
+                    }
+                }
+                "-l" | "--layers" => parsed.layers = Some(Self::parse_layers(&value(&arg)?)?),
+                "-c" | "--color" => {
+                    parsed.color_mode = match value(&arg)?.as_str() {
+                        "auto" => ColorMode::Auto,
+                        "always" => ColorMode::Always,
+                        "never" => ColorMode::Never,
+                        other => return Err(format!("Unknown color mode '{}'", other)),
+                    }
+                }
+                "-a" | "--area" => parsed.area = Some(Self::parse_area(&value(&arg)?)?),
+                "-m" | "--metrics" => parsed.metrics_filename = Some(value(&arg)?),
+                "--images" => parsed.images_directory = Some(value(&arg)?),
+                "--frames" => parsed.frames_directory = Some(value(&arg)?),
+                "--frame-duration" => {
+                    parsed.frame_duration = Some(Self::parse_positive(&arg, &value(&arg)?)?)
+                }
+                "--image-pollution" => parsed.image_pollution = true,
+                "-s" | "--save" => parsed.save_filename = Some(value(&arg)?),
+                "--resume" => parsed.resume_filename = Some(value(&arg)?),
+                _ if arg.starts_with('-') => return Err(format!("Unknown option '{}'", arg)),
+                _ if parsed.config_filename.is_none() => parsed.config_filename = Some(arg),
+                _ => return Err(format!("Unexpected argument '{}'", arg)),
+            }
+        }
+
+        if !parsed.help {
+            parsed.check_sources()?;
+        }
+        Ok(parsed)
+    }
+
+    /// Checks that the arguments name exactly one way to set up the
+    /// simulation: a config file, a region layout with the required
+    /// settings, or a snapshot.
+    fn check_sources(&self) -> Result<(), String> {
+        let has_config = self.config_filename.is_some();
+        let has_region = self.region_layout_filename.is_some();
+
+        if self.resume_filename.is_some() {
+            if has_config || has_region {
+                return Err(
+                    "'--resume' cannot be combined with a config file or '--region'".into(),
+                );
+            }
+        } else if !has_config {
+            if !has_region {
+                return Err("Expected a config file, '--region' or '--resume'".into());
+            }
+            if self.time_limit.is_none() {
+                return Err("'--time-limit' is required without a config file".into());
+            }
+            if self.refresh_rate.is_none() {
+                return Err("'--refresh-rate' is required without a config file".into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_positive(name: &str, value: &str) -> Result<u32, String> {
+        match value.parse() {
+            Ok(number) if number > 0 => Ok(number),
+            _ => Err(format!(
+                "'{}' expects a positive whole number, got '{}'",
+                name, value
+            )),
+        }
+    }
+
+    fn parse_layers(value: &str) -> Result<Vec<Layer>, String> {
+        value
+            .split(',')
+            .map(|name| {
+                Layer::from_name(name.trim()).ok_or_else(|| format!("Unknown layer '{}'", name))
+            })
+            .collect()
+    }
+
+    fn parse_area(value: &str) -> Result<Area, String> {
+        let coordinates: Vec<u32> = value
+            .split(',')
+            .map(|part| part.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("Invalid area '{}'", value))?;
+
+        match coordinates[..] {
+            [x1, y1, x2, y2] => Ok(Area::new((x1, y1), (x2, y2))),
+            _ => Err(format!("Invalid area '{}', expected X1,Y1,X2,Y2", value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Args, String> {
+        Args::parse(args.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn parses_options_and_the_config_file() {
+        let args = parse(
+            "config1.txt -t 20 --refresh-rate 2 -o json -l power,land-value -c never -a 3,4,1,0 -s out.txt",
+        )
+        .unwrap();
+
+        assert_eq!(args.config_filename.as_deref(), Some("config1.txt"));
+        assert_eq!(args.time_limit, Some(20));
+        assert_eq!(args.refresh_rate, Some(2));
+        assert_eq!(args.output_mode, OutputMode::Json);
+        assert_eq!(args.layers, Some(vec![Layer::Power, Layer::LandValue]));
+        assert_eq!(args.color_mode, ColorMode::Never);
+        assert_eq!(args.area, Some(Area::new((1, 0), (3, 4))));
+        assert_eq!(args.save_filename.as_deref(), Some("out.txt"));
+    }
+
+    #[test]
+    fn accepts_a_region_with_the_required_settings() {
+        let args = parse("-r region.csv -t 5 -f 1").unwrap();
+
+        assert_eq!(args.config_filename, None);
+        assert_eq!(args.region_layout_filename.as_deref(), Some("region.csv"));
+    }
+
+    #[test]
+    fn requires_settings_without_a_config_file() {
+        assert!(parse("-r region.csv").unwrap_err().contains("--time-limit"));
+        assert!(parse("-r region.csv -t 5")
+            .unwrap_err()
+            .contains("--refresh-rate"));
+        assert!(parse("-o final").is_err());
+    }
+
+    #[test]
+    fn rejects_resume_with_another_source() {
+        assert!(parse("--resume snapshot.txt -t 40").is_ok());
+        assert!(parse("--resume snapshot.txt config1.txt").is_err());
+        assert!(parse("--resume snapshot.txt -r region.csv -t 5 -f 1").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert_eq!(
+            parse("config1.txt -t 0").unwrap_err(),
+            "'-t' expects a positive whole number, got '0'"
+        );
+        assert!(parse("config1.txt -o verbose").is_err());
+        assert!(parse("config1.txt -l zoning,traffic").is_err());
+        assert!(parse("config1.txt -a 1,2,3").is_err());
+        assert!(parse("config1.txt --metrics").is_err());
+        assert!(parse("config1.txt --unknown").is_err());
+        assert!(parse("config1.txt config2.txt").is_err());
+    }
+
+    #[test]
+    fn help_needs_no_config() {
+        assert!(parse("--help").unwrap().help);
+    }
+}
@@ -1,100 +1,387 @@
-use std::{error::Error, fs, io};
+use crate::{
+    error::{Error, Result},
+    rules::GrowthRules,
+    zone::ZoneRegistry,
+};
+use std::{fmt, fs, io};
 
-#[derive(Debug)]
+/// Time steps remembered to detect cycles unless the config sets
+/// `Cycle Detection Window`.
+pub const DEFAULT_CYCLE_DETECTION_WINDOW: u32 = 32;
+
+#[derive(Debug, Clone)]
 pub struct Config {
     pub region_layout_filename: String,
     pub time_limit: u32,
     pub refresh_rate: u32,
+    /// Power every plant can supply per time step, `None` for unlimited.
+    /// Each populated zone demands one unit of power per population.
+    pub power_plant_capacity: Option<u32>,
+    /// How many cells away pollution spreads, `None` for no limit.
+    pub pollution_spread_radius: Option<u32>,
+    /// How much pollution drops with every cell it spreads.
+    pub pollution_falloff: u32,
+    /// How much pollution every cell loses per time step before new
+    /// pollution is spread.
+    pub pollution_decay: u32,
+    /// Pollution at which residential zones stop growing, `None` to never
+    /// stop.
+    pub pollution_growth_limit: Option<u32>,
+    /// Pollution at which residential zones lose one population per time
+    /// step, `None` to never decline. With `decline_delay` set, residential
+    /// zones at the limit are in distress and only decline after the delay.
+    pub pollution_decline_limit: Option<u32>,
+    /// Time steps a zone has to be in distress before it starts losing
+    /// population, `None` to never decline.
+    pub decline_delay: Option<u32>,
+    /// Time steps a declined zone stays empty and in distress before it is
+    /// abandoned, `None` to never abandon zones.
+    pub abandonment_delay: Option<u32>,
+    /// How many past time steps are remembered to detect a region cycling
+    /// through the same states, 0 to only detect steady states.
+    pub cycle_detection_window: u32,
+    /// The `Growth Rules` file the built-in zones were loaded from, `None`
+    /// for the built-in rules.
+    pub growth_rules_filename: Option<String>,
+    /// The zones a region layout may contain. The built-in zones grow by
+    /// the rules loaded from the `Growth Rules` file.
+    pub zones: ZoneRegistry,
 }
 
 impl Config {
-    pub fn from_user_input() -> Self {
-        let config_filename = Self::request_config_filename();
-
-        let (region_layout_filename, time_limit, refresh_rate) =
-            match Self::read_config_file(&config_filename) {
-                Ok(data) => data,
-                Err(e) => {
-                    println!("Error: {}", e);
-                    return Self::from_user_input();
-                }
-            };
-
+    /// Creates a config with the required settings and the defaults for all
+    /// optional ones.
+    pub fn new(region_layout_filename: &str, time_limit: u32, refresh_rate: u32) -> Self {
         Config {
-            region_layout_filename,
+            region_layout_filename: region_layout_filename.to_string(),
             time_limit,
             refresh_rate,
+            power_plant_capacity: None,
+            pollution_spread_radius: None,
+            pollution_falloff: 1,
+            pollution_decay: 0,
+            pollution_growth_limit: None,
+            pollution_decline_limit: None,
+            decline_delay: None,
+            abandonment_delay: None,
+            cycle_detection_window: DEFAULT_CYCLE_DETECTION_WINDOW,
+            growth_rules_filename: None,
+            zones: ZoneRegistry::default(),
+        }
+    }
+
+    pub fn from_file(config_filename: &str) -> Result<Self> {
+        let contents = fs::read_to_string(config_filename).map_err(|source| Error::Io {
+            filename: config_filename.to_string(),
+            source,
+        })?;
+
+        Self::parse(config_filename, &contents)
+    }
+
+    /// Parses the `Key:Value` lines of a config file. Blank lines and lines
+    /// starting with `#` are skipped, unknown keys are ignored.
+    pub fn parse(config_filename: &str, contents: &str) -> Result<Self> {
+        let mut config = Config::new("", 0, 0);
+        let mut region_layout_filename = None;
+        let mut time_limit = None;
+        let mut refresh_rate = None;
+
+        for setting in Setting::parse_all(config_filename, contents) {
+            let setting = setting?;
+
+            match setting.key {
+                "Region Layout" => {
+                    if !Self::validate_file_extension(setting.value, ".csv") {
+                        return Err(setting.invalid("a .csv file"));
+                    }
+                    region_layout_filename = Some(setting.value.to_string());
+                }
+                "Time Limit" => time_limit = Some(setting.positive()?),
+                "Refresh Rate" => refresh_rate = Some(setting.positive()?),
+                "Power Plant Capacity" => config.power_plant_capacity = Some(setting.positive()?),
+                "Pollution Spread Radius" => {
+                    config.pollution_spread_radius = Some(setting.whole()?)
+                }
+                "Pollution Falloff" => config.pollution_falloff = setting.positive()?,
+                "Pollution Decay" => config.pollution_decay = setting.whole()?,
+                "Pollution Growth Limit" => {
+                    config.pollution_growth_limit = Some(setting.positive()?)
+                }
+                "Pollution Decline Limit" => {
+                    config.pollution_decline_limit = Some(setting.positive()?)
+                }
+                "Decline Delay" => config.decline_delay = Some(setting.positive()?),
+                "Abandonment Delay" => config.abandonment_delay = Some(setting.whole()?),
+                "Cycle Detection Window" => config.cycle_detection_window = setting.whole()?,
+                "Growth Rules" => {
+                    if !Self::validate_file_extension(setting.value, ".txt") {
+                        return Err(setting.invalid("a .txt file"));
+                    }
+                    config.zones = ZoneRegistry::new(&GrowthRules::from_file(setting.value)?);
+                    config.growth_rules_filename = Some(setting.value.to_string());
+                }
+                _ => {}
+            }
+        }
+
+        let missing_key = |key: &str| Error::MissingKey {
+            filename: config_filename.to_string(),
+            key: key.to_string(),
+        };
+
+        config.region_layout_filename =
+            region_layout_filename.ok_or_else(|| missing_key("Region Layout"))?;
+        config.time_limit = time_limit.ok_or_else(|| missing_key("Time Limit"))?;
+        config.refresh_rate = refresh_rate.ok_or_else(|| missing_key("Refresh Rate"))?;
+
+        Ok(config)
+    }
+
+    /// Asks for config files on stdin until one loads. Returns `None` if
+    /// stdin ends before that.
+    pub fn from_user_input() -> Option<Self> {
+        loop {
+            let config_filename = Self::request_config_filename()?;
+
+            match Self::from_file(&config_filename) {
+                Ok(config) => return Some(config),
+                Err(e) => println!("Error: {}", e),
+            }
         }
     }
 
-    pub fn reinitialize(&mut self) {
-        let new_config = Self::from_user_input();
-        self.region_layout_filename = new_config.region_layout_filename;
-        self.time_limit = new_config.time_limit;
-        self.refresh_rate = new_config.refresh_rate;
+    /// Replaces the config with one entered on stdin. Returns `false`, and
+    /// keeps the config, if stdin ends before a config loads.
+    pub fn reinitialize(&mut self) -> bool {
+        match Self::from_user_input() {
+            Some(config) => {
+                *self = config;
+                true
+            }
+            None => false,
+        }
     }
 
-    fn request_config_filename() -> String {
+    fn request_config_filename() -> Option<String> {
         println!("Please input a valid config file(.txt): ");
-        let mut filename = String::new();
-        io::stdin()
-            .read_line(&mut filename)
-            .expect("Failed to read line");
 
-        while !Self::validate_file_extension(&filename, ".txt") {
+        loop {
+            let mut filename = String::new();
+            if io::stdin()
+                .read_line(&mut filename)
+                .expect("Failed to read line")
+                == 0
+            {
+                return None;
+            }
+
+            if Self::validate_file_extension(&filename, ".txt") {
+                return Some(filename.trim().to_string());
+            }
+
             println!("Error. Invalid File Format.");
             println!("Please input a valid config file(.txt)");
-            filename.clear();
-            io::stdin()
-                .read_line(&mut filename)
-                .expect("Failed to read line");
+        }
+    }
+
+    fn validate_file_extension(filename: &str, extension: &str) -> bool {
+        if filename.trim().len() < extension.len() {
+            return false;
+        }
+
+        filename.trim().ends_with(extension)
+    }
+}
+
+/// Writes the config in the config file format, leaving out optional
+/// settings at their defaults.
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Region Layout:{}", self.region_layout_filename)?;
+        writeln!(f, "Time Limit:{}", self.time_limit)?;
+        writeln!(f, "Refresh Rate:{}", self.refresh_rate)?;
+
+        let optional = [
+            ("Power Plant Capacity", self.power_plant_capacity),
+            ("Pollution Spread Radius", self.pollution_spread_radius),
+            (
+                "Pollution Falloff",
+                Some(self.pollution_falloff).filter(|&falloff| falloff != 1),
+            ),
+            (
+                "Pollution Decay",
+                Some(self.pollution_decay).filter(|&decay| decay != 0),
+            ),
+            ("Pollution Growth Limit", self.pollution_growth_limit),
+            ("Pollution Decline Limit", self.pollution_decline_limit),
+            ("Decline Delay", self.decline_delay),
+            ("Abandonment Delay", self.abandonment_delay),
+            (
+                "Cycle Detection Window",
+                Some(self.cycle_detection_window)
+                    .filter(|&window| window != DEFAULT_CYCLE_DETECTION_WINDOW),
+            ),
+        ];
+        for (key, value) in optional {
+            if let Some(value) = value {
+                writeln!(f, "{}:{}", key, value)?;
+            }
         }
 
-        filename.trim().to_string()
+        if let Some(growth_rules_filename) = &self.growth_rules_filename {
+            writeln!(f, "Growth Rules:{}", growth_rules_filename)?;
+        }
+
+        Ok(())
     }
+}
 
-    fn read_config_file(config_filename: &str) -> Result<(String, u32, u32), Box<dyn Error>> {
-        let contents = fs::read_to_string(config_filename)
-            .map_err(|_| format!("Couldn't open \"{}\"", config_filename))?;
+/// A single `Key:Value` line of a settings file, with the key and value
+/// trimmed. Used by the config file as well as the growth rules file.
+pub(crate) struct Setting<'a> {
+    pub filename: &'a str,
+    pub line: usize,
+    pub column: usize,
+    pub key: &'a str,
+    pub value: &'a str,
+}
 
-        let mut result: (String, u32, u32) = (String::new(), 0, 0);
+impl<'a> Setting<'a> {
+    /// Splits a settings file into its settings. Blank lines and lines
+    /// starting with `#` are skipped.
+    pub fn parse_all(
+        filename: &'a str,
+        contents: &'a str,
+    ) -> impl Iterator<Item = Result<Setting<'a>>> + 'a {
         contents
             .lines()
-            .map(|line| line.split_once(':').unwrap())
-            .for_each(|(key, value)| {
-                if key == "Region Layout" {
-                    result.0 = value.trim().to_string();
-                } else if key == "Time Limit" {
-                    result.1 = value.trim().parse().unwrap();
-                } else if key == "Refresh Rate" {
-                    result.2 = value.trim().parse().unwrap();
-                }
-            });
+            .enumerate()
+            .filter(|(_, line)| {
+                let trimmed = line.trim();
+                !trimmed.is_empty() && !trimmed.starts_with('#')
+            })
+            .map(move |(index, line)| {
+                let (key, value) = line.split_once(':').ok_or_else(|| Error::Syntax {
+                    filename: filename.to_string(),
+                    line: index + 1,
+                    column: line.len() - line.trim_start().len() + 1,
+                    message: format!("Expected 'Key:Value', found \"{}\"", line.trim()),
+                })?;
 
-        if result.0.is_empty() || !Self::validate_file_extension(&result.0, ".csv") {
-            return Err(format!("Missing 'Region Layout' in \"{}\"", config_filename).into());
-        }
-        if result.1 == 0 {
-            return Err(
-                format!("Missing or invalid 'Time Limit' in \"{}\"", config_filename).into(),
-            );
+                let value_start = key.len() + 1;
+                Ok(Setting {
+                    filename,
+                    line: index + 1,
+                    column: value_start + value.len() - value.trim_start().len() + 1,
+                    key: key.trim(),
+                    value: value.trim(),
+                })
+            })
+    }
+
+    pub fn invalid(&self, expected: &str) -> Error {
+        Error::InvalidValue {
+            filename: self.filename.to_string(),
+            line: self.line,
+            column: self.column,
+            key: self.key.to_string(),
+            value: self.value.to_string(),
+            expected: expected.to_string(),
         }
-        if result.2 == 0 {
-            return Err(format!(
-                "Missing or invalid 'Refresh Rate' in \"{}\"",
-                config_filename
-            )
-            .into());
+    }
+
+    pub fn positive(&self) -> Result<u32> {
+        match self.value.parse::<u32>() {
+            Ok(number) if number > 0 => Ok(number),
+            _ => Err(self.invalid("a positive whole number")),
         }
+    }
 
-        Ok(result)
+    pub fn whole(&self) -> Result<u32> {
+        self.value
+            .parse::<u32>()
+            .map_err(|_| self.invalid("a whole number"))
     }
 
-    fn validate_file_extension(filename: &str, extension: &str) -> bool {
-        if filename.trim().len() < extension.len() {
-            return false;
+    pub fn integer(&self) -> Result<i32> {
+        self.value
+            .parse::<i32>()
+            .map_err(|_| self.invalid("a whole number, optionally negative"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_settings_and_skips_comments() {
+        let contents = "# Comment\n\nRegion Layout: region.csv\n  Time Limit :  20  \n";
+        let settings: Vec<_> = Setting::parse_all("config.txt", contents)
+            .collect::<Result<_>>()
+            .unwrap();
+
+        let summary: Vec<_> = settings
+            .iter()
+            .map(|setting| (setting.line, setting.column, setting.key, setting.value))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (3, 16, "Region Layout", "region.csv"),
+                (4, 17, "Time Limit", "20")
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_lines_without_a_colon() {
+        let mut settings = Setting::parse_all("config.txt", "Time Limit:5\n  Refresh Rate 1\n");
+
+        assert!(settings.next().unwrap().is_ok());
+        match settings.next().unwrap() {
+            Err(Error::Syntax {
+                filename,
+                line,
+                column,
+                ..
+            }) => assert_eq!((filename.as_str(), line, column), ("config.txt", 2, 3)),
+            _ => panic!("expected a syntax error"),
         }
+    }
 
-        filename.trim().ends_with(extension)
+    #[test]
+    fn points_invalid_values_at_the_value() {
+        let error = Config::parse(
+            "config.txt",
+            "Region Layout:region.csv\nTime Limit: 0\nRefresh Rate:1",
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            Error::InvalidValue { line: 2, column: 13, ref key, .. } if key == "Time Limit"
+        ));
+        assert_eq!(
+            error.to_string(),
+            "config.txt:2:13: Invalid 'Time Limit' value \"0\", expected a positive whole number"
+        );
+    }
+
+    #[test]
+    fn requires_the_region_layout_time_limit_and_refresh_rate() {
+        let error =
+            Config::parse("config.txt", "Region Layout:region.csv\nTime Limit:5").unwrap_err();
+
+        assert!(matches!(error, Error::MissingKey { ref key, .. } if key == "Refresh Rate"));
+    }
+
+    #[test]
+    fn writes_the_config_file_format() {
+        let contents = "Region Layout:region.csv\nTime Limit:20\nRefresh Rate:2\nPower Plant Capacity:4\nDecline Delay:3\n";
+        let config = Config::parse("config.txt", contents).unwrap();
+
+        assert_eq!(config.to_string(), contents);
     }
 }
@@ -0,0 +1,98 @@
+use crate::validation::LayoutIssue;
+use std::{fmt, io};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors raised while loading a config file or a region layout, or while
+/// registering zones.
+///
+/// Line and column numbers start at 1 and point at the offending text so the
+/// message can be used to fix the file directly.
+#[derive(Debug)]
+pub enum Error {
+    /// The file could not be read.
+    Io { filename: String, source: io::Error },
+    /// A line does not follow the expected format.
+    Syntax {
+        filename: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// A key has a value that could not be used.
+    InvalidValue {
+        filename: String,
+        line: usize,
+        column: usize,
+        key: String,
+        value: String,
+        expected: String,
+    },
+    /// A required key does not appear in the file.
+    MissingKey { filename: String, key: String },
+    /// The region layout failed validation. Holds every problem found,
+    /// including warnings.
+    InvalidLayout {
+        filename: String,
+        issues: Vec<LayoutIssue>,
+    },
+    /// A zone was registered with an infrastructure symbol.
+    ReservedSymbol { symbol: char },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { filename, source } => {
+                write!(f, "Couldn't open \"{}\": {}", filename, source)
+            }
+            Error::Syntax {
+                filename,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", filename, line, column, message),
+            Error::InvalidValue {
+                filename,
+                line,
+                column,
+                key,
+                value,
+                expected,
+            } => write!(
+                f,
+                "{}:{}:{}: Invalid '{}' value \"{}\", expected {}",
+                filename, line, column, key, value, expected
+            ),
+            Error::MissingKey { filename, key } => {
+                write!(f, "{}: Missing '{}'", filename, key)
+            }
+            Error::InvalidLayout { filename, issues } => {
+                write!(
+                    f,
+                    "Found {} problem(s) in the region layout \"{}\":",
+                    issues.len(),
+                    filename
+                )?;
+                for issue in issues {
+                    write!(f, "\n  {}:{}", filename, issue)?;
+                }
+                Ok(())
+            }
+            Error::ReservedSymbol { symbol } => write!(
+                f,
+                "'{}' is an infrastructure symbol and cannot be a zone",
+                symbol
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
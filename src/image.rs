@@ -0,0 +1,475 @@
+use crate::{
+    error::{Error, Result},
+    map::Map,
+    map_cell::MapCell,
+    map_grid::MapGrid,
+    render,
+    zone::ZoneRegistry,
+};
+use std::{fmt, fs, path::Path};
+
+/// Width and height of a cell in the image, in pixels.
+pub const DEFAULT_TILE_SIZE: u32 = 24;
+/// How long every frame is shown in the animation, in milliseconds.
+pub const DEFAULT_FRAME_DURATION: u32 = 500;
+
+const BACKGROUND: &str = "#f4f1ea";
+const GRID_LINE: &str = "#ffffff";
+/// Color of the pollution heatmap, drawn over the cells with an opacity
+/// growing with their pollution.
+const HEATMAP: &str = "#ff2a00";
+/// Opacity of the heatmap over the most polluted cell.
+const MAX_HEATMAP_OPACITY: f64 = 0.7;
+/// Opacity of the tiles of unpopulated zones.
+const EMPTY_ZONE_OPACITY: f64 = 0.3;
+
+/// A [`MapGrid`] drawn as an SVG image, with one colored tile per cell.
+///
+/// Tiles use the colors of the colored zoning layer: zones are tinted by type
+/// and shaded by population, and roads, powerlines and power plants each get
+/// their own color. Every tile is labelled like the printed region, with the
+/// glyphs of the zones in `zones`. An optional heatmap tints every cell red
+/// by its pollution.
+pub struct MapImage<'a> {
+    grid: &'a MapGrid,
+    zones: &'a ZoneRegistry,
+    tile_size: u32,
+    pollution: bool,
+}
+
+impl<'a> MapImage<'a> {
+    pub fn new(grid: &'a MapGrid, zones: &'a ZoneRegistry) -> Self {
+        MapImage {
+            grid,
+            zones,
+            tile_size: DEFAULT_TILE_SIZE,
+            pollution: false,
+        }
+    }
+
+    pub fn with_tile_size(mut self, tile_size: u32) -> Self {
+        self.tile_size = tile_size.max(1);
+        self
+    }
+
+    /// Draws the pollution heatmap over the cells.
+    pub fn with_pollution(mut self, pollution: bool) -> Self {
+        self.pollution = pollution;
+        self
+    }
+
+    pub fn save(&self, image_filename: &str) -> Result<()> {
+        fs::write(image_filename, self.to_string()).map_err(|source| Error::Io {
+            filename: image_filename.to_string(),
+            source,
+        })
+    }
+
+    fn write_cell(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        cell: &MapCell,
+        max_pollution: u32,
+    ) -> fmt::Result {
+        let size = self.tile_size;
+        let (x, y) = (cell.position.0 * size, cell.position.1 * size);
+        let is_zone = cell.cell_type.is_zone();
+
+        if let Some(color) = render::palette_color(cell) {
+            let opacity = if is_zone && cell.population == 0 && !cell.is_abandoned {
+                EMPTY_ZONE_OPACITY
+            } else {
+                1.0
+            };
+            writeln!(
+                f,
+                r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" fill-opacity="{}"/>"#,
+                x,
+                y,
+                size,
+                size,
+                hex(color),
+                opacity
+            )?;
+        }
+
+        if self.pollution && cell.pollution > 0 {
+            let opacity = MAX_HEATMAP_OPACITY * cell.pollution as f64 / max_pollution as f64;
+            writeln!(
+                f,
+                r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" fill-opacity="{:.2}"/>"#,
+                x, y, size, size, HEATMAP, opacity
+            )?;
+        }
+
+        let label = self.zones.glyph(cell);
+        if label.trim().is_empty() {
+            return Ok(());
+        }
+        // Light text on the dark tiles of abandoned zones, dark text
+        // everywhere else.
+        let text_color = if is_zone && cell.is_abandoned {
+            "#ffffff"
+        } else {
+            "#202020"
+        };
+        writeln!(
+            f,
+            r#"<text x="{}" y="{}" fill="{}">{}</text>"#,
+            x + size / 2,
+            y + size / 2,
+            text_color,
+            escape(&label)
+        )
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (
+            self.grid.width() as u32 * self.tile_size,
+            self.grid.height() as u32 * self.tile_size,
+        )
+    }
+
+    /// Writes the tiles and the lines between them, without the enclosing
+    /// `svg` element.
+    fn write_tiles(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let size = self.tile_size;
+        let (width, height) = self.size();
+        let max_pollution = self
+            .grid
+            .cells()
+            .iter()
+            .map(|cell| cell.pollution)
+            .max()
+            .unwrap_or(0);
+
+        writeln!(
+            f,
+            r#"<rect width="{}" height="{}" fill="{}"/>"#,
+            width, height, BACKGROUND
+        )?;
+
+        writeln!(
+            f,
+            r#"<g font-family="monospace" font-size="{}" text-anchor="middle" dominant-baseline="central">"#,
+            size / 2
+        )?;
+        for cell in self.grid.cells() {
+            self.write_cell(f, cell, max_pollution)?;
+        }
+        writeln!(f, "</g>")?;
+
+        // Thin lines between the tiles.
+        writeln!(f, r#"<g stroke="{}" stroke-width="1">"#, GRID_LINE)?;
+        for column in 1..self.grid.width() as u32 {
+            writeln!(
+                f,
+                r#"<line x1="{x}" y1="0" x2="{x}" y2="{}"/>"#,
+                height,
+                x = column * size
+            )?;
+        }
+        for row in 1..self.grid.height() as u32 {
+            writeln!(
+                f,
+                r#"<line x1="0" y1="{y}" x2="{}" y2="{y}"/>"#,
+                width,
+                y = row * size
+            )?;
+        }
+        writeln!(f, "</g>")
+    }
+}
+
+/// Writes the image as a standalone SVG document.
+impl fmt::Display for MapImage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_svg_start(f, self.size())?;
+        self.write_tiles(f)?;
+        writeln!(f, "</svg>")
+    }
+}
+
+fn write_svg_start(f: &mut fmt::Formatter<'_>, (width, height): (u32, u32)) -> fmt::Result {
+    writeln!(
+        f,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = width,
+        h = height
+    )
+}
+
+/// Records images of the initial state, every refresh state and the final
+/// state of a simulation, and writes them to a directory as `initial.svg`,
+/// `state-001.svg` and so on, and `final.svg`.
+#[derive(Debug, Clone)]
+pub struct ImageRecorder {
+    zones: ZoneRegistry,
+    pollution: bool,
+    /// The recorded grids, each with the name of its image without the
+    /// extension.
+    states: Vec<(String, MapGrid)>,
+}
+
+impl ImageRecorder {
+    /// Creates a recorder drawing the zones in `zones`, with the pollution
+    /// heatmap if `pollution` is set.
+    pub fn new(zones: &ZoneRegistry, pollution: bool) -> Self {
+        ImageRecorder {
+            zones: zones.clone(),
+            pollution,
+            states: Vec::new(),
+        }
+    }
+
+    pub fn record_initial(&mut self, map: &Map) {
+        self.states
+            .push(("initial".to_string(), map.current.clone()));
+    }
+
+    pub fn record_state(&mut self, state: u32, map: &Map) {
+        self.states
+            .push((format!("state-{:03}", state), map.current.clone()));
+    }
+
+    /// Records the current state as the final state, in place of any final
+    /// state recorded before.
+    pub fn record_final(&mut self, map: &Map) {
+        self.states.retain(|(name, _)| name != "final");
+        self.states.push(("final".to_string(), map.current.clone()));
+    }
+
+    /// Writes every recorded image to `directory`, creating it if needed.
+    pub fn save(&self, directory: &str) -> Result<()> {
+        fs::create_dir_all(directory).map_err(|source| Error::Io {
+            filename: directory.to_string(),
+            source,
+        })?;
+
+        for (name, grid) in &self.states {
+            let image_filename = Path::new(directory).join(format!("{}.svg", name));
+            MapImage::new(grid, &self.zones)
+                .with_pollution(self.pollution)
+                .save(&image_filename.to_string_lossy())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Records a frame of every refresh state of a simulation, and writes them
+/// as a numbered frame sequence (`frame-0000.svg`, `frame-0001.svg` and so
+/// on) together with `animation.svg`, which plays all frames in a loop.
+///
+/// The first frame is the initial state and the last one the final state,
+/// so growth can be reviewed after a run without any output.
+#[derive(Debug, Clone)]
+pub struct FrameRecorder {
+    zones: ZoneRegistry,
+    pollution: bool,
+    /// How long every frame is shown in the animation, in milliseconds.
+    frame_duration: u32,
+    /// The recorded grids, each with its time step.
+    frames: Vec<(u32, MapGrid)>,
+}
+
+impl FrameRecorder {
+    /// Creates a recorder drawing the zones in `zones`, with the pollution
+    /// heatmap if `pollution` is set, and showing every frame for
+    /// `frame_duration` milliseconds in the animation.
+    pub fn new(zones: &ZoneRegistry, pollution: bool, frame_duration: u32) -> Self {
+        FrameRecorder {
+            zones: zones.clone(),
+            pollution,
+            frame_duration: frame_duration.max(1),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Records the current state of the map as the next frame, unless it
+    /// was already recorded at this time step.
+    pub fn record(&mut self, time_step: u32, map: &Map) {
+        if self.frames.last().map(|(last, _)| *last) != Some(time_step) {
+            self.frames.push((time_step, map.current.clone()));
+        }
+    }
+
+    /// Writes every frame and the animation to `directory`, creating it if
+    /// needed.
+    pub fn save(&self, directory: &str) -> Result<()> {
+        fs::create_dir_all(directory).map_err(|source| Error::Io {
+            filename: directory.to_string(),
+            source,
+        })?;
+        let write = |name: &str, contents: String| {
+            let filename = Path::new(directory).join(name);
+            fs::write(&filename, contents).map_err(|source| Error::Io {
+                filename: filename.to_string_lossy().into_owned(),
+                source,
+            })
+        };
+
+        for (index, (_, grid)) in self.frames.iter().enumerate() {
+            let image = MapImage::new(grid, &self.zones).with_pollution(self.pollution);
+            write(&format!("frame-{:04}.svg", index), image.to_string())?;
+        }
+        write("animation.svg", Animation(self).to_string())
+    }
+}
+
+/// All frames of a [`FrameRecorder`] in a single SVG document, shown one
+/// after another in an endless loop.
+struct Animation<'a>(&'a FrameRecorder);
+
+impl fmt::Display for Animation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let recorder = self.0;
+        let Some((_, first)) = recorder.frames.first() else {
+            return writeln!(f, r#"<svg xmlns="http://www.w3.org/2000/svg"/>"#);
+        };
+        let count = recorder.frames.len();
+        let duration = recorder.frame_duration as usize * count;
+
+        write_svg_start(f, MapImage::new(first, &recorder.zones).size())?;
+        for (index, (time_step, grid)) in recorder.frames.iter().enumerate() {
+            // Every frame is hidden except during its own slice of the
+            // loop. Discrete animations hold each value until the next key
+            // time, so only the times the frame shows and hides are needed.
+            let (values, key_times) = match (index, index + 1 == count) {
+                (0, true) => ("inline;inline".to_string(), "0;1".to_string()),
+                (0, false) => (
+                    "inline;none".to_string(),
+                    format!("0;{}", fraction(1, count)),
+                ),
+                (_, true) => (
+                    "none;inline".to_string(),
+                    format!("0;{}", fraction(index, count)),
+                ),
+                _ => (
+                    "none;inline;none".to_string(),
+                    format!(
+                        "0;{};{}",
+                        fraction(index, count),
+                        fraction(index + 1, count)
+                    ),
+                ),
+            };
+
+            writeln!(
+                f,
+                "<g display=\"{}\">",
+                if index == 0 { "inline" } else { "none" }
+            )?;
+            writeln!(f, "<title>Time step {}</title>", time_step)?;
+            writeln!(
+                f,
+                r#"<animate attributeName="display" values="{}" keyTimes="{}" dur="{}ms" calcMode="discrete" repeatCount="indefinite"/>"#,
+                values, key_times, duration
+            )?;
+            MapImage::new(grid, &recorder.zones)
+                .with_pollution(recorder.pollution)
+                .write_tiles(f)?;
+            writeln!(f, "</g>")?;
+        }
+        writeln!(f, "</svg>")
+    }
+}
+
+fn fraction(numerator: usize, denominator: usize) -> String {
+    format!("{:.6}", numerator as f64 / denominator as f64)
+}
+
+/// The `#rrggbb` form of a 256 color palette color.
+fn hex(color: u8) -> String {
+    let (red, green, blue) = render::rgb(color);
+    format!("#{:02x}{:02x}{:02x}", red, green, blue)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, simulation::Simulation, zone::ZoneBehavior};
+
+    #[derive(Debug)]
+    struct Park;
+
+    impl ZoneBehavior for Park {
+        fn symbol(&self) -> char {
+            'K'
+        }
+
+        fn name(&self) -> &str {
+            "Park"
+        }
+
+        fn grow(
+            &self,
+            _: &mut MapCell,
+            _: &MapCell,
+            _: &MapGrid,
+            workers: i32,
+            goods: i32,
+        ) -> (i32, i32) {
+            (workers, goods)
+        }
+
+        fn resources(&self, _: &MapCell) -> (i32, i32) {
+            (0, 0)
+        }
+
+        fn glyph(&self, _: &MapCell) -> String {
+            "&".to_string()
+        }
+    }
+
+    #[test]
+    fn labels_zones_with_their_glyphs() {
+        let mut zones = ZoneRegistry::default();
+        zones.register(Park).unwrap();
+        let map = Map::parse("region.csv", "P,T,K\nR,T,-", &zones).unwrap();
+
+        let image = MapImage::new(&map.current, &map.zones).to_string();
+
+        assert!(image.starts_with("<svg"));
+        assert!(image.contains(">&amp;</text>"));
+        assert!(image.contains(">R</text>"));
+        assert!(image.contains(">P</text>"));
+        assert_eq!(image.matches("<text").count(), 6);
+    }
+
+    #[test]
+    fn records_the_final_state_of_a_stopped_simulation() {
+        let zones = ZoneRegistry::default();
+        let map = Map::parse("region.csv", "P,T,R", &zones).unwrap();
+        let mut simulation = Simulation::new(Config::new("region.csv", 10, 2), map)
+            .with_images(ImageRecorder::new(&zones, false))
+            .with_frames(FrameRecorder::new(&zones, false, DEFAULT_FRAME_DURATION));
+        assert!(!simulation.step().is_finished());
+
+        simulation.finish_recording();
+        simulation.finish_recording();
+
+        let images = simulation.images().unwrap();
+        let names: Vec<&str> = images
+            .states
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, ["initial", "final"]);
+        assert_eq!(images.states[1].1, simulation.map().current);
+
+        let frames = simulation.frames().unwrap();
+        let time_steps: Vec<u32> = frames
+            .frames
+            .iter()
+            .map(|(time_step, _)| *time_step)
+            .collect();
+        assert_eq!(time_steps, [0, 1]);
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt::{self, Write};
+
+/// A value that can be written as JSON.
+pub(crate) trait ToJson {
+    fn write_json(&self, out: &mut String);
+}
+
+impl ToJson for u32 {
+    fn write_json(&self, out: &mut String) {
+        let _ = write!(out, "{}", self);
+    }
+}
+
+impl ToJson for i32 {
+    fn write_json(&self, out: &mut String) {
+        let _ = write!(out, "{}", self);
+    }
+}
+
+impl ToJson for usize {
+    fn write_json(&self, out: &mut String) {
+        let _ = write!(out, "{}", self);
+    }
+}
+
+impl ToJson for bool {
+    fn write_json(&self, out: &mut String) {
+        out.push_str(if *self { "true" } else { "false" });
+    }
+}
+
+impl ToJson for str {
+    fn write_json(&self, out: &mut String) {
+        out.push('"');
+        for c in self.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if c.is_control() => {
+                    let _ = write!(out, "\\u{:04x}", c as u32);
+                }
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+}
+
+impl ToJson for String {
+    fn write_json(&self, out: &mut String) {
+        self.as_str().write_json(out);
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn write_json(&self, out: &mut String) {
+        match self {
+            Some(value) => value.write_json(out),
+            None => out.push_str("null"),
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for [T] {
+    fn write_json(&self, out: &mut String) {
+        out.push('[');
+        for (index, value) in self.iter().enumerate() {
+            if index > 0 {
+                out.push(',');
+            }
+            value.write_json(out);
+        }
+        out.push(']');
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn write_json(&self, out: &mut String) {
+        self.as_slice().write_json(out);
+    }
+}
+
+/// A JSON object built up one field at a time, in insertion order.
+#[derive(Debug, Clone)]
+pub(crate) struct JsonObject {
+    fields: String,
+}
+
+impl JsonObject {
+    pub fn new() -> Self {
+        JsonObject {
+            fields: String::new(),
+        }
+    }
+
+    pub fn field(mut self, key: &str, value: &(impl ToJson + ?Sized)) -> Self {
+        if !self.fields.is_empty() {
+            self.fields.push(',');
+        }
+        key.write_json(&mut self.fields);
+        self.fields.push(':');
+        value.write_json(&mut self.fields);
+        self
+    }
+}
+
+impl ToJson for JsonObject {
+    fn write_json(&self, out: &mut String) {
+        out.push('{');
+        out.push_str(&self.fields);
+        out.push('}');
+    }
+}
+
+/// Writes the object on a single line, as a JSON Lines record.
+impl fmt::Display for JsonObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{{}}}", self.fields)
+    }
+}
@@ -0,0 +1,51 @@
+//! Urban growth simulation engine.
+//!
+//! A region is loaded from a CSV layout into a [`Map`], configured through a
+//! [`Config`] and advanced one time step at a time by a [`Simulation`]:
+//!
+//! ```no_run
+//! use simcity::{Config, Map, Simulation};
+//!
+//! let config = Config::from_file("config1.txt").unwrap();
+//! let map = Map::from_file(&config.region_layout_filename, &config.zones).unwrap();
+//!
+//! let mut simulation = Simulation::new(config, map);
+//! while !simulation.step().is_finished() {}
+//!
+//! let map = simulation.map();
+//! let stats = Map::get_area_stats(&map.current, &map.zones, &map.area());
+//! println!("Total population: {}", stats.total_population());
+//! ```
+
+pub mod config;
+pub mod error;
+pub mod image;
+mod json;
+pub mod map;
+pub mod map_cell;
+pub mod map_grid;
+pub mod metrics;
+pub mod power;
+pub mod render;
+pub mod road;
+pub mod rules;
+pub mod simulation;
+pub mod snapshot;
+pub mod validation;
+pub mod zone;
+
+pub use config::Config;
+pub use error::{Error, Result};
+pub use image::{FrameRecorder, ImageRecorder, MapImage};
+pub use map::{Area, AreaStats, Map};
+pub use map_cell::{CellType, MapCell};
+pub use map_grid::MapGrid;
+pub use metrics::{Metrics, MetricsRecorder};
+pub use power::PowerNetwork;
+pub use render::{ColorMode, Layer, MapView};
+pub use road::RoadNetwork;
+pub use rules::{GrowthRules, GrowthThreshold, ZoneRules};
+pub use simulation::{OutputMode, Simulation, StepOutcome};
+pub use snapshot::Snapshot;
+pub use validation::{LayoutIssue, Severity};
+pub use zone::{RuleZone, ZoneBehavior, ZoneRegistry};
@@ -1,16 +1,167 @@
-use crate::config::Config;
-use crate::map::Map;
-use crate::simulation::Simulation;
+use crate::cli::{Args, USAGE};
+use simcity::{image, ColorMode, Config, FrameRecorder, ImageRecorder, Map, Simulation, Snapshot};
+use std::{env, error::Error, process::ExitCode};
 
-mod config;
-mod map;
-mod map_cell;
-mod simulation;
+mod cli;
+mod tui;
 
-fn main() {
-    let mut config = Config::from_user_input();
-    let mut map = Map::from_config(&mut config);
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() {
+        return run_interactive();
+    }
 
-    let mut simulation = Simulation::new(&config, &mut map);
+    let args = match Args::parse(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    if args.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(1)
+        }
+    }
+}
+
+fn run_interactive() -> ExitCode {
+    let Some(mut config) = Config::from_user_input() else {
+        eprintln!("Error: No valid config file was entered");
+        return ExitCode::from(1);
+    };
+    let Some(map) = Map::from_config(&mut config) else {
+        eprintln!("Error: No valid region layout was entered");
+        return ExitCode::from(1);
+    };
+    print_warnings(&config, &map);
+
+    let mut simulation = Simulation::new(config, map).with_color(ColorMode::Auto.enabled());
     simulation.start();
+    simulation.analyze_area();
+    ExitCode::SUCCESS
+}
+
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let simulation = match &args.resume_filename {
+        Some(snapshot_filename) => resume(snapshot_filename, args)?,
+        None => load(args)?,
+    };
+
+    if let Some(area) = &args.area {
+        Map::validate_area(&simulation.map().current, area)?;
+    }
+
+    let mut simulation = simulation
+        .with_output_mode(args.output_mode)
+        .with_color(args.color_mode.enabled());
+    if let Some(layers) = &args.layers {
+        simulation = simulation.with_layers(layers.clone());
+    }
+    if args.metrics_filename.is_some() {
+        simulation = simulation.with_metrics();
+    }
+    if args.images_directory.is_some() {
+        let images = ImageRecorder::new(&simulation.map().zones, args.image_pollution);
+        simulation = simulation.with_images(images);
+    }
+    if args.frames_directory.is_some() {
+        let frame_duration = args.frame_duration.unwrap_or(image::DEFAULT_FRAME_DURATION);
+        let frames = FrameRecorder::new(
+            &simulation.map().zones,
+            args.image_pollution,
+            frame_duration,
+        );
+        simulation = simulation.with_frames(frames);
+    }
+
+    if args.interactive {
+        let layers = args.layers.as_deref().unwrap_or(&[]);
+        tui::run(&mut simulation, layers, args.color_mode.enabled())?;
+        simulation.finish_recording();
+        simulation.end();
+    } else {
+        simulation.start();
+    }
+    if let (Some(metrics_filename), Some(metrics)) = (&args.metrics_filename, simulation.metrics())
+    {
+        metrics.save(metrics_filename)?;
+    }
+    if let (Some(images_directory), Some(images)) = (&args.images_directory, simulation.images()) {
+        images.save(images_directory)?;
+    }
+    if let (Some(frames_directory), Some(frames)) = (&args.frames_directory, simulation.frames()) {
+        frames.save(frames_directory)?;
+    }
+    if let Some(snapshot_filename) = &args.save_filename {
+        Snapshot::new(&simulation).save(snapshot_filename)?;
+    }
+    if let Some(area) = &args.area {
+        simulation.print_area_analysis(area);
+    }
+
+    Ok(())
+}
+
+/// Sets up a new simulation from the config file or the command line.
+fn load(args: &Args) -> Result<Simulation, Box<dyn Error>> {
+    // `Args::parse` checked that the region layout comes with the required
+    // settings when there is no config file.
+    let mut config = match (
+        &args.config_filename,
+        &args.region_layout_filename,
+        args.time_limit,
+        args.refresh_rate,
+    ) {
+        (Some(config_filename), ..) => Config::from_file(config_filename)?,
+        (None, Some(region_layout_filename), Some(time_limit), Some(refresh_rate)) => {
+            Config::new(region_layout_filename, time_limit, refresh_rate)
+        }
+        _ => unreachable!("Args::parse checks the config sources"),
+    };
+
+    if let Some(region_layout_filename) = &args.region_layout_filename {
+        config.region_layout_filename = region_layout_filename.clone();
+    }
+    apply_overrides(&mut config, args);
+
+    let map = Map::from_file(&config.region_layout_filename, &config.zones)?;
+    print_warnings(&config, &map);
+
+    Ok(Simulation::new(config, map))
+}
+
+/// Continues a saved simulation, with the settings given on the command
+/// line overriding the saved ones.
+fn resume(snapshot_filename: &str, args: &Args) -> Result<Simulation, Box<dyn Error>> {
+    let mut snapshot = Snapshot::from_file(snapshot_filename)?;
+    apply_overrides(&mut snapshot.config, args);
+
+    Ok(snapshot.resume()?)
+}
+
+fn apply_overrides(config: &mut Config, args: &Args) {
+    if let Some(time_limit) = args.time_limit {
+        config.time_limit = time_limit;
+    }
+    if let Some(refresh_rate) = args.refresh_rate {
+        config.refresh_rate = refresh_rate;
+    }
+    if args.power_plant_capacity.is_some() {
+        config.power_plant_capacity = args.power_plant_capacity;
+    }
+}
+
+fn print_warnings(config: &Config, map: &Map) {
+    for warning in &map.warnings {
+        eprintln!("{}:{}", config.region_layout_filename, warning);
+    }
 }
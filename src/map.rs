@@ -1,278 +1,498 @@
 use crate::{
     config::Config,
+    error::{Error, Result},
     map_cell::{CellType, MapCell},
+    map_grid::MapGrid,
+    power::PowerNetwork,
+    render::MapView,
+    road::{self, RoadNetwork},
+    validation::{self, LayoutIssue},
+    zone::{ZoneBehavior, ZoneRegistry},
 };
-use std::{cell::RefCell, fmt, fs, rc::Rc};
-
-pub type MapGrid = Vec<Vec<Rc<RefCell<MapCell>>>>;
+use std::{cmp::Reverse, fmt, fs};
 
+/// The region, double buffered: every time step grows `current` from the
+/// state stored in `previous`.
 pub struct Map {
     pub current: MapGrid,
-    pub previous: Option<MapGrid>,
+    pub previous: MapGrid,
+    /// The power grid traced from the region's power plants.
+    pub power: PowerNetwork,
+    /// The districts of zones connected by roads.
+    pub roads: RoadNetwork,
+    /// Non-fatal problems found while validating the region layout.
+    pub warnings: Vec<LayoutIssue>,
+    /// The zones the region was loaded with.
+    pub zones: ZoneRegistry,
+}
+
+/// A rectangular area of the region, stored with its corners normalized so
+/// that `(x1, y1)` is the top-left and `(x2, y2)` the bottom-right cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Area {
+    pub x1: u32,
+    pub y1: u32,
+    pub x2: u32,
+    pub y2: u32,
+}
+
+impl Area {
+    pub fn new((x1, y1): (u32, u32), (x2, y2): (u32, u32)) -> Self {
+        Area {
+            x1: x1.min(x2),
+            y1: y1.min(y2),
+            x2: x1.max(x2),
+            y2: y1.max(y2),
+        }
+    }
+
+    pub fn contains(&self, (x, y): (u32, u32)) -> bool {
+        x >= self.x1 && x <= self.x2 && y >= self.y1 && y <= self.y2
+    }
+}
+
+/// Population and pollution totals for a single [`Area`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AreaStats {
+    /// Name and population of every zone type, in the order of
+    /// [`ZoneRegistry::zones`].
+    pub populations: Vec<(String, i32)>,
+    pub pollution: i32,
+}
+
+impl AreaStats {
+    /// The population of the zone type called `name`, e.g. `"Residential"`.
+    pub fn population(&self, name: &str) -> Option<i32> {
+        self.populations
+            .iter()
+            .find(|(zone_name, _)| zone_name == name)
+            .map(|(_, population)| *population)
+    }
+
+    pub fn total_population(&self) -> i32 {
+        self.populations
+            .iter()
+            .map(|(_, population)| population)
+            .sum()
+    }
 }
 
 impl Map {
-    pub fn from_config(config: &mut Config) -> Self {
-        let contents = match fs::read_to_string(&config.region_layout_filename) {
-            Ok(content) => content,
-            Err(_) => {
-                println!(
-                    "Error: Couldn't find \"{}\". Please make sure the Region Layout file exists",
-                    config.region_layout_filename
-                );
-                config.reinitialize();
-                return Self::from_config(config);
+    /// Loads the region layout of `config`, asking for another config on
+    /// stdin while the layout is invalid. Returns `None` if stdin ends before
+    /// a valid layout is loaded.
+    pub fn from_config(config: &mut Config) -> Option<Self> {
+        loop {
+            match Self::from_file(&config.region_layout_filename, &config.zones) {
+                Ok(map) => return Some(map),
+                Err(e) => {
+                    println!("Error: {}", e);
+                    println!("Please make sure the Region Layout file is valid");
+                    if !config.reinitialize() {
+                        return None;
+                    }
+                }
             }
-        };
+        }
+    }
 
-        let grid: MapGrid = contents
-            .lines()
+    pub fn from_file(region_layout_filename: &str, zones: &ZoneRegistry) -> Result<Self> {
+        let contents = fs::read_to_string(region_layout_filename).map_err(|source| Error::Io {
+            filename: region_layout_filename.to_string(),
+            source,
+        })?;
+
+        Self::parse(region_layout_filename, &contents, zones)
+    }
+
+    /// Parses a region layout, where every comma separated field is a single
+    /// cell symbol: either infrastructure or one of the `zones`. The layout
+    /// is validated first and all problems found are returned together.
+    pub fn parse(
+        region_layout_filename: &str,
+        contents: &str,
+        zones: &ZoneRegistry,
+    ) -> Result<Self> {
+        let check = validation::validate_layout(contents, zones);
+        if check.has_errors() {
+            return Err(Error::InvalidLayout {
+                filename: region_layout_filename.to_string(),
+                issues: check.issues,
+            });
+        }
+
+        let height = check.symbols.len();
+        let width = check.symbols[0].len();
+        let cells = check
+            .symbols
+            .iter()
             .enumerate()
-            .map(|(y, line)| {
-                line.split(',')
-                    .enumerate()
-                    .map(|(x, cell_char)| {
-                        Rc::new(RefCell::new(MapCell {
-                            position: (x as u32, y as u32),
-                            cell_type: match cell_char {
-                                "R" => CellType::Residential('R'),
-                                "C" => CellType::Commercial('C'),
-                                "I" => CellType::Industrial('I'),
-                                _ => CellType::Other(cell_char.chars().next().unwrap()),
-                            },
-                            pollution: 0,
-                            population: 0,
-                            is_powerline_adjacent: false,
-                            neighbors: vec![],
-                        }))
-                    })
-                    .collect()
+            .flat_map(|(y, row)| {
+                row.iter().enumerate().map(move |(x, symbol)| {
+                    let symbol = symbol.expect("validated layout has only known symbols");
+                    MapCell::new((x as u32, y as u32), zones.cell_type(symbol))
+                })
             })
             .collect();
 
-        let map = Map {
+        let mut grid = MapGrid::new(width, height, cells);
+        let power = Self::trace_power(&mut grid);
+        let roads = Self::trace_roads(&mut grid);
+
+        Ok(Map {
+            previous: grid.clone(),
             current: grid,
-            previous: None,
-        };
-        Self::track_adjacency(&map.current);
+            power,
+            roads,
+            warnings: check.warnings(),
+            zones: zones.clone(),
+        })
+    }
 
-        map
+    /// The area covering the whole region.
+    pub fn area(&self) -> Area {
+        let width = self.current.width() as u32;
+        let height = self.current.height() as u32;
+
+        Area::new((0, 0), (width.saturating_sub(1), height.saturating_sub(1)))
     }
 
-    pub fn spread_pollution(&mut self) {
-        // Collect all cells into a vector
-        let mut cells: Vec<Rc<RefCell<MapCell>>> = self
-            .current
-            .iter()
-            .flatten()
-            .map(|cell| Rc::clone(cell))
-            .collect();
+    /// Lowers the pollution of every cell by `decay`, down to 0.
+    pub fn decay_pollution(grid: &mut MapGrid, decay: u32) {
+        if decay == 0 {
+            return;
+        }
 
-        // Sort cells by pollution level in descending order
-        cells.sort_by(|a, b| b.borrow().pollution.cmp(&a.borrow().pollution));
+        for cell in grid.cells_mut() {
+            cell.pollution = cell.pollution.saturating_sub(decay);
+        }
+    }
 
-        // Process cells with pollution >= 2
-        for cell in cells {
-            let pollution_level = cell.borrow().pollution;
-            if pollution_level < 2 {
+    /// Spreads the pollution emitted by every cell to the cells around it.
+    /// Pollution drops by `Pollution Falloff` with every cell of distance and
+    /// reaches at most `Pollution Spread Radius` cells away. Cells keep their
+    /// pollution if it is already higher.
+    pub fn spread_pollution(grid: &mut MapGrid, zones: &ZoneRegistry, config: &Config) {
+        let radius = config.pollution_spread_radius;
+        let falloff = config.pollution_falloff.max(1);
+        let (width, height) = (grid.width(), grid.height());
+
+        for index in 0..grid.cells().len() {
+            let cell = &grid.cells()[index];
+            let emission = zones
+                .for_cell(cell)
+                .map_or(0, |zone| zone.pollution_emission(cell));
+            if emission == 0 {
                 continue;
             }
 
-            // Get weak references to neighbors and process them
-            let neighbors = cell.borrow().neighbors.clone();
-            for neighbor_weak in &neighbors {
-                if let Some(neighbor) = neighbor_weak.upgrade() {
-                    let neighbor_pollution = neighbor.borrow().pollution;
-                    if neighbor_pollution < pollution_level - 1 {
-                        neighbor.borrow_mut().pollution = pollution_level - 1;
-                    }
+            // Emission - falloff * distance has to stay above 0.
+            let reach = ((emission - 1) / falloff).min(radius.unwrap_or(u32::MAX)) as usize;
+            let (x, y) = grid.cells()[index].position;
+            let (x, y) = (x as usize, y as usize);
+
+            for ny in y.saturating_sub(reach)..=(y + reach).min(height - 1) {
+                for nx in x.saturating_sub(reach)..=(x + reach).min(width - 1) {
+                    let distance = nx.abs_diff(x).max(ny.abs_diff(y)) as u32;
+                    let pollution = emission - falloff * distance;
+
+                    let index = grid.index(nx, ny);
+                    let cell = &mut grid.cells_mut()[index];
+                    cell.pollution = cell.pollution.max(pollution);
                 }
             }
         }
     }
 
-    pub fn step(&mut self) {
-        let mut cells: Vec<Rc<RefCell<MapCell>>> = self
-            .current
-            .iter()
-            .flatten()
-            .map(|cell| Rc::clone(cell))
-            .collect();
+    pub fn step(&mut self, config: &Config) {
+        let order = Self::growth_order(&self.previous, &self.zones);
+        self.supply_power(&order, config.power_plant_capacity);
 
-        cells.sort_by(|a, b| a.borrow().cmp(&b.borrow()));
+        let previous = &self.previous;
 
-        let previous = &self.previous.as_ref().unwrap();
+        // Workers and goods are only shared within a district.
+        let resources = Self::get_district_resources(previous, &self.roads, &self.zones);
+        let mut remaining = resources.clone();
 
-        let available_workers = Self::get_available_workers(previous);
-        let available_goods = Self::get_available_goods(previous);
+        for index in order {
+            let previous_cell = &previous.cells()[index];
+            let (Some(district), Some(zone)) = (
+                self.roads.district(index),
+                self.zones.for_cell(previous_cell),
+            ) else {
+                continue;
+            };
 
-        let mut remaining_workers = available_workers;
-        let mut remaining_goods = available_goods;
+            let cell = &mut self.current.cells_mut()[index];
+            cell.is_at_capacity = !self.power.can_grow(index);
 
-        for cell in cells {
-            let (x, y) = cell.borrow().position;
-            let remainders = cell.borrow_mut().grow(
-                &previous[y as usize][x as usize].borrow(),
+            let is_distressed =
+                Self::is_distressed(previous_cell, zone, resources[district], config);
+            if cell.decline(previous_cell, is_distressed, config) {
+                continue;
+            }
+
+            let (remaining_workers, remaining_goods) = remaining[district];
+
+            remaining[district] = cell.grow(
+                previous_cell,
+                previous,
+                zone,
+                config,
                 remaining_workers,
                 remaining_goods,
             );
+            if cell.population > previous_cell.population {
+                self.power.reserve(index);
+            }
+        }
+
+        Self::decay_pollution(&mut self.current, config.pollution_decay);
+        Self::spread_pollution(&mut self.current, &self.zones, config);
+    }
 
-            remaining_workers = remainders.0;
-            remaining_goods = remainders.1;
+    /// Whether a zone is browned out, short of the workers or goods it needs
+    /// given the available `(workers, goods)` of its district, or pollution
+    /// sensitive and at the pollution decline limit.
+    fn is_distressed(
+        cell: &MapCell,
+        zone: &dyn ZoneBehavior,
+        (workers, goods): (i32, i32),
+        config: &Config,
+    ) -> bool {
+        let is_polluted = zone.is_pollution_sensitive()
+            && config
+                .pollution_decline_limit
+                .is_some_and(|limit| cell.pollution >= limit);
+        let (needs_workers, needs_goods) = zone.needs();
+        let is_short = (needs_workers && workers < 0) || (needs_goods && goods < 0);
+
+        cell.is_browned_out || is_polluted || is_short
+    }
+
+    pub fn get_abandoned_zones(grid: &MapGrid) -> i32 {
+        grid.cells().iter().filter(|cell| cell.is_abandoned).count() as i32
+    }
+
+    /// Supplies the zones from their plants in growth order, based on the
+    /// previous state, and copies the resulting power state to the current
+    /// state.
+    fn supply_power(&mut self, order: &[usize], capacity: Option<u32>) {
+        self.power.supply(&mut self.previous, order, capacity);
+
+        for &index in order {
+            let previous = self.previous.cells()[index];
+            let cell = &mut self.current.cells_mut()[index];
+            cell.is_powered = previous.is_powered;
+            cell.is_browned_out = previous.is_browned_out;
         }
     }
 
-    pub fn update_previous(&mut self) {
-        let grid = &self.current;
-        // Create deep clone with new MapCell instances
-        let deep_clone: MapGrid = grid
+    pub fn get_browned_out_zones(grid: &MapGrid) -> i32 {
+        grid.cells()
             .iter()
-            .map(|row| {
-                row.iter()
-                    .map(|cell| {
-                        let cell_ref = cell.borrow();
-                        Rc::new(RefCell::new(MapCell {
-                            position: cell_ref.position,
-                            cell_type: cell_ref.cell_type.clone(),
-                            pollution: cell_ref.pollution,
-                            population: cell_ref.population,
-                            is_powerline_adjacent: cell_ref.is_powerline_adjacent,
-                            neighbors: vec![], // We'll rebuild neighbor references below
-                        }))
-                    })
-                    .collect()
-            })
+            .filter(|cell| cell.is_browned_out)
+            .count() as i32
+    }
+
+    /// Indices of the zoned cells in the order they grow in:
+    /// 1. Lower zone growth priority first: commercial cells before
+    ///    industrial cells before residential cells
+    /// 2. Larger population first
+    /// 3. Greater adjacent population first
+    /// 4. Smaller Y first
+    /// 5. Smaller X first
+    fn growth_order(grid: &MapGrid, zones: &ZoneRegistry) -> Vec<usize> {
+        let mut order: Vec<usize> = grid
+            .cells()
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| zones.for_cell(cell).is_some())
+            .map(|(index, _)| index)
             .collect();
 
-        self.previous = Some(deep_clone);
+        order.sort_by_cached_key(|&index| {
+            let cell = &grid.cells()[index];
+            let priority = zones
+                .for_cell(cell)
+                .map_or(u8::MAX, |zone| zone.growth_priority());
+            let (x, y) = cell.position;
+            let adjacent_population = grid.count_adjacent_population(x as usize, y as usize);
+
+            (
+                priority,
+                Reverse(cell.population),
+                Reverse(adjacent_population),
+                y,
+                x,
+            )
+        });
+
+        order
+    }
+
+    /// Copies the current state into the previous buffer, reusing its
+    /// allocation.
+    pub fn update_previous(&mut self) {
+        self.previous.clone_from(&self.current);
+    }
 
-        // Rebuild neighbor references in the cloned map
-        if let Some(prev_grid) = &self.previous {
-            Self::track_adjacency(prev_grid);
+    /// Traces the power grid from the power plants and marks the zones it
+    /// reaches as powered.
+    pub fn trace_power(grid: &mut MapGrid) -> PowerNetwork {
+        let power = PowerNetwork::trace(grid);
+        for (index, cell) in grid.cells_mut().iter_mut().enumerate() {
+            cell.is_powered = power.is_powered(index);
         }
+
+        power
     }
 
-    pub fn track_adjacency(grid: &MapGrid) {
-        let height = grid.len();
-
-        for y in 0..height {
-            let width = grid[y].len();
-            for x in 0..width {
-                let cell = &grid[y][x];
-                let mut neighbors = Vec::new();
-
-                // Check all 8 adjacent cells
-                for dy in -1..=1 {
-                    for dx in -1..=1 {
-                        if dx == 0 && dy == 0 {
-                            continue;
-                        } // Skip self
-
-                        let nx = x as isize + dx;
-                        let ny = y as isize + dy;
-
-                        // Check bounds
-                        if nx >= 0 && nx < width as isize && ny >= 0 && ny < height as isize {
-                            // Add weak reference to neighbor
-                            let neighbor = &grid[ny as usize][nx as usize];
-                            let neighbor_cell = neighbor.borrow();
-                            let is_powerline = neighbor_cell.cell_type == CellType::Other('T')
-                                || neighbor_cell.cell_type == CellType::Other('#');
-
-                            if is_powerline && !cell.borrow().is_powerline_adjacent {
-                                cell.borrow_mut().is_powerline_adjacent = true;
-                            }
-                            neighbors.push(Rc::downgrade(neighbor));
-                        }
-                    }
+    /// Traces the road network and marks the zones next to a road.
+    pub fn trace_roads(grid: &mut MapGrid) -> RoadNetwork {
+        let roads = RoadNetwork::trace(grid);
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                let is_road_adjacent = grid.neighbors(x, y).any(road::is_road);
+                if let Some(cell) = grid.get_mut(x, y) {
+                    cell.is_road_adjacent = is_road_adjacent;
                 }
-
-                cell.borrow_mut().neighbors = neighbors;
             }
         }
+
+        roads
     }
 
     pub fn get_population(grid: &MapGrid, cell_type: Option<&CellType>) -> i32 {
         let mut total_population = 0;
 
-        for row in grid {
-            for cell_rc in row {
-                let cell = cell_rc.borrow();
-                if let Some(cell_type) = &cell_type {
-                    if &cell.cell_type != *cell_type {
-                        continue;
-                    }
+        for cell in grid.cells() {
+            if let Some(cell_type) = &cell_type {
+                if &cell.cell_type != *cell_type {
+                    continue;
                 }
-                total_population += cell.population as i32;
             }
+            total_population += cell.population as i32;
         }
 
         total_population
     }
 
-    pub fn get_available_workers(grid: &MapGrid) -> i32 {
-        let mut total_workers = 0;
-        let mut taken_workers = 0;
+    pub fn get_available_workers(grid: &MapGrid, zones: &ZoneRegistry) -> i32 {
+        grid.cells()
+            .iter()
+            .map(|cell| Self::resource_balance(cell, zones).0)
+            .sum()
+    }
 
-        for row in grid {
-            for cell_rc in row {
-                let cell = cell_rc.borrow();
-                match &cell.cell_type {
-                    CellType::Residential(_) => {
-                        total_workers += cell.population as i32;
-                    }
-                    CellType::Industrial(_) => {
-                        taken_workers += (cell.population * 2) as i32;
-                    }
-                    CellType::Commercial(_) => {
-                        taken_workers += cell.population as i32;
-                    }
-                    _ => {}
-                }
+    pub fn get_available_goods(grid: &MapGrid, zones: &ZoneRegistry) -> i32 {
+        grid.cells()
+            .iter()
+            .map(|cell| Self::resource_balance(cell, zones).1)
+            .sum()
+    }
+
+    /// Available workers and goods of every district, indexed by district.
+    pub fn get_district_resources(
+        grid: &MapGrid,
+        roads: &RoadNetwork,
+        zones: &ZoneRegistry,
+    ) -> Vec<(i32, i32)> {
+        let mut resources = vec![(0, 0); roads.district_count()];
+
+        for (index, cell) in grid.cells().iter().enumerate() {
+            if let Some(district) = roads.district(index) {
+                let (workers, goods) = Self::resource_balance(cell, zones);
+                resources[district].0 += workers;
+                resources[district].1 += goods;
             }
         }
 
-        total_workers - taken_workers
+        resources
     }
 
-    pub fn get_available_goods(grid: &MapGrid) -> i32 {
-        let mut total_goods = 0;
-        let mut sold_goods = 0;
+    /// Workers the cell provides minus the workers it employs, and goods it
+    /// produces minus the goods it sells.
+    fn resource_balance(cell: &MapCell, zones: &ZoneRegistry) -> (i32, i32) {
+        zones
+            .for_cell(cell)
+            .map_or((0, 0), |zone| zone.resources(cell))
+    }
 
-        for row in grid {
-            for cell_rc in row {
-                let cell = cell_rc.borrow();
-                match &cell.cell_type {
-                    CellType::Industrial(_) => {
-                        total_goods += cell.population as i32;
-                    }
-                    CellType::Commercial(_) => {
-                        sold_goods += cell.population as i32;
-                    }
-                    _ => {}
-                }
-            }
-        }
+    /// How desirable a cell is to live or work in: the population around
+    /// it, plus 1 each for power and road access, minus its pollution.
+    pub fn land_value(grid: &MapGrid, cell: &MapCell) -> u32 {
+        let amenities = cell.is_powered as u32 + cell.is_road_adjacent as u32;
 
-        total_goods - sold_goods
+        (cell.count_adjacent_population(grid) + amenities).saturating_sub(cell.pollution)
     }
 
     pub fn total_pollution(grid: &MapGrid) -> i32 {
         let mut total_pollution = 0;
 
-        for row in grid {
-            for cell_rc in row {
-                let cell = cell_rc.borrow();
-                total_pollution += cell.pollution as i32;
-            }
+        for cell in grid.cells() {
+            total_pollution += cell.pollution as i32;
         }
 
         total_pollution
     }
 
+    pub fn validate_area(grid: &MapGrid, area: &Area) -> std::result::Result<(), String> {
+        let width = grid.width() as u32;
+        let height = grid.height() as u32;
+
+        if area.x2 >= width || area.y2 >= height {
+            return Err(format!(
+                "Area ({}, {})-({}, {}) is outside the region (MaxX = {}, MaxY = {})",
+                area.x1,
+                area.y1,
+                area.x2,
+                area.y2,
+                width.saturating_sub(1),
+                height.saturating_sub(1)
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn get_area_stats(grid: &MapGrid, zones: &ZoneRegistry, area: &Area) -> AreaStats {
+        let cells: Vec<&MapCell> = grid
+            .cells()
+            .iter()
+            .filter(|cell| area.contains(cell.position))
+            .collect();
+
+        let populations = zones
+            .zones()
+            .map(|zone| {
+                let population = cells
+                    .iter()
+                    .filter(|cell| {
+                        zones
+                            .for_cell(cell)
+                            .is_some_and(|cell_zone| cell_zone.symbol() == zone.symbol())
+                    })
+                    .map(|cell| cell.population as i32)
+                    .sum();
+                (zone.name().to_string(), population)
+            })
+            .collect();
+
+        AreaStats {
+            populations,
+            pollution: cells.iter().map(|cell| cell.pollution as i32).sum(),
+        }
+    }
+
+    /// What a cell looks like on the printed map.
+    pub fn glyph(&self, cell: &MapCell) -> String {
+        self.zones.glyph(cell)
+    }
+
     pub fn print_pollution(grid: &MapGrid) {
         // Print top border
-        let width = grid[0].len();
+        let width = grid.width();
         println!();
         for _ in 0..width {
             print!("----");
@@ -280,10 +500,10 @@ impl Map {
         println!("--");
 
         // Print each row with pollution values
-        for row in grid {
+        for row in grid.rows() {
             print!("|"); // Left border
             for cell in row {
-                let poll = cell.borrow().pollution;
+                let poll = cell.pollution;
                 // Format pollution as fixed width of 3 characters
                 print!(" {:<3}", poll);
             }
@@ -301,30 +521,6 @@ impl Map {
 
 impl fmt::Display for Map {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let grid = &self.current;
-        let width = grid[0].len();
-
-        // Print top border
-        for _ in 0..width {
-            write!(f, "----")?;
-        }
-        writeln!(f, "--")?;
-
-        // Print each row with cells
-        for row in grid {
-            write!(f, "|")?; // Left border
-            for cell in row {
-                // Use left-padded fixed width of 4 characters
-                let cell_str = format!("{}", cell.borrow());
-                write!(f, " {:<3}", cell_str)?;
-            }
-            writeln!(f, "|")?; // Right border without extra space
-        }
-
-        // Print bottom border
-        for _ in 0..width {
-            write!(f, "----")?;
-        }
-        write!(f, "--")
+        write!(f, "{}", MapView::new(self))
     }
 }
@@ -1,15 +1,14 @@
-use std::{
-    cell::RefCell,
-    cmp::Ordering,
-    fmt::{Display, Formatter, Result},
-    rc::Weak,
-};
+use crate::{config::Config, map_grid::MapGrid, zone::ZoneBehavior};
+use std::fmt::{Display, Formatter, Result};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CellType {
     Residential(char),
     Industrial(char),
     Commercial(char),
+    /// A zone registered in the [`ZoneRegistry`](crate::zone::ZoneRegistry)
+    /// beyond the built-in ones.
+    Custom(char),
     Other(char),
 }
 
@@ -19,209 +18,172 @@ impl CellType {
             CellType::Residential(c) => *c,
             CellType::Industrial(c) => *c,
             CellType::Commercial(c) => *c,
+            CellType::Custom(c) => *c,
             CellType::Other(c) => *c,
         }
     }
+
+    /// Whether the cell is a zone registered in the
+    /// [`ZoneRegistry`](crate::zone::ZoneRegistry) rather than
+    /// infrastructure or empty land.
+    pub fn is_zone(&self) -> bool {
+        !matches!(self, CellType::Other(_))
+    }
 }
 
-#[derive(Debug)]
+/// Shown in place of an abandoned zone.
+pub const ABANDONED_SYMBOL: char = 'X';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct MapCell {
     pub position: (u32, u32),
     pub cell_type: CellType,
     pub population: u32,
     pub pollution: u32,
-    pub is_powerline_adjacent: bool,
-    pub neighbors: Vec<Weak<RefCell<MapCell>>>,
+    /// Whether the zone is connected to a power plant through the power grid.
+    pub is_powered: bool,
+    /// Whether the zone is on the power grid but its plant has no capacity
+    /// left for it. Browned out zones cannot grow.
+    pub is_browned_out: bool,
+    /// Whether the plant of the zone had no capacity left for it to grow
+    /// into at its turn in the last time step. Such zones keep their power
+    /// but cannot grow.
+    pub is_at_capacity: bool,
+    /// Whether the zone is next to a road. Zones without road access cannot
+    /// grow beyond the first level.
+    pub is_road_adjacent: bool,
+    /// Consecutive time steps the zone has been in distress: browned out,
+    /// short of workers or goods, or pollution sensitive and at the
+    /// pollution decline limit.
+    /// Only tracked when decline is enabled.
+    pub distress: u32,
+    /// Consecutive time steps the zone has been empty and in distress.
+    /// Only tracked when abandonment is enabled.
+    pub empty_for: u32,
+    /// Whether the zone has been left empty by a long distress. Abandoned
+    /// zones cannot grow until the distress ends.
+    pub is_abandoned: bool,
 }
 
 impl MapCell {
-    pub fn grow(&mut self, previous: &MapCell, workers: i32, goods: i32) -> (i32, i32) {
-        match self.cell_type {
-            CellType::Residential(_) => Self::residential_grow(self, previous, workers, goods),
-            CellType::Commercial(_) => Self::commercial_grow(self, previous, workers, goods),
-            CellType::Industrial(_) => Self::industrial_grow(self, previous, workers, goods),
-            CellType::Other(_) => (workers, goods),
+    pub fn new(position: (u32, u32), cell_type: CellType) -> Self {
+        MapCell {
+            position,
+            cell_type,
+            population: 0,
+            pollution: 0,
+            is_powered: false,
+            is_browned_out: false,
+            is_at_capacity: false,
+            is_road_adjacent: false,
+            distress: 0,
+            empty_for: 0,
+            is_abandoned: false,
         }
     }
 
-    fn residential_grow(
-        cell: &mut MapCell,
-        previous: &MapCell,
-        workers: i32,
-        goods: i32,
-    ) -> (i32, i32) {
-        if previous.population == 0 {
-            if previous.is_powerline_adjacent || previous.count_adjacent_population() >= 1 {
-                cell.population = 1;
-            }
+    /// Tracks how long the zone has been in distress and applies the
+    /// configured decline rules: after `Decline Delay` time steps of distress
+    /// the zone loses one population per time step, and once it is empty
+    /// for another `Abandonment Delay` time steps it is abandoned.
+    ///
+    /// Returns whether the zone declined or is abandoned, in which case it
+    /// does not grow this time step.
+    pub fn decline(&mut self, previous: &MapCell, is_distressed: bool, config: &Config) -> bool {
+        let Some(decline_delay) = config.decline_delay else {
+            return false;
+        };
+
+        // Stop counting once the rule applies, so a region in constant
+        // distress can still reach a steady state.
+        self.distress = if is_distressed {
+            (previous.distress + 1).min(decline_delay)
         } else {
-            let target_adjacents;
+            0
+        };
 
-            match previous.population {
-                1 => {
-                    target_adjacents = 2;
-                }
-                2 => {
-                    target_adjacents = 4;
-                }
-                3 => {
-                    target_adjacents = 6;
-                }
-                4 => {
-                    target_adjacents = 8;
-                }
-                _ => return (workers, goods),
-            }
-
-            let mut remaining_adjacents = target_adjacents;
-
-            for cell_weak in &previous.neighbors {
-                if let Some(neighbor) = cell_weak.upgrade() {
-                    let neighbor = neighbor.borrow();
-
-                    if neighbor.population >= previous.population {
-                        remaining_adjacents -= 1;
-                    }
-
-                    if remaining_adjacents == 0 {
-                        cell.population = previous.population + 1;
-                        break;
-                    }
-                }
-            }
+        let declined = self.distress >= decline_delay && previous.population > 0;
+        if declined {
+            self.population = previous.population - 1;
         }
 
-        (workers, goods)
-    }
-
-    fn commercial_grow(
-        cell: &mut MapCell,
-        previous: &MapCell,
-        workers: i32,
-        goods: i32,
-    ) -> (i32, i32) {
-        // Early return if not enough resources
-        if workers < 1 || goods < 1 {
-            return (workers, goods);
-        }
-
-        let mut workers_used = workers;
-        let mut goods_used = goods;
-
-        if previous.population == 0 && previous.is_powerline_adjacent {
-            cell.population = 1;
-            workers_used -= 1;
-            goods_used -= 1;
+        let Some(abandonment_delay) = config.abandonment_delay else {
+            return declined;
+        };
+        self.empty_for = if is_distressed && previous.population == 0 {
+            (previous.empty_for + 1).min(abandonment_delay)
         } else {
-            let mut target_adjacents;
-            let target_population;
-
-            match previous.population {
-                0 => {
-                    target_adjacents = 1;
-                    target_population = 1;
-                }
-                1 => {
-                    target_adjacents = 2;
-                    target_population = 1;
-                }
-                _ => return (workers, goods),
-            }
+            0
+        };
+        self.is_abandoned = self.population == 0
+            && self.distress >= decline_delay
+            && self.empty_for >= abandonment_delay;
 
-            for cell_weak in &previous.neighbors {
-                if let Some(neighbor) = cell_weak.upgrade() {
-                    let neighbor = neighbor.borrow();
-
-                    if neighbor.population >= target_population {
-                        target_adjacents -= 1;
-                    }
-
-                    if target_adjacents == 0 {
-                        cell.population = previous.population + 1;
-                        workers_used -= 1;
-                        goods_used -= 1;
-                        break;
-                    }
-                }
-            }
-        }
-
-        (workers_used, goods_used)
+        declined || self.is_abandoned
     }
 
-    fn industrial_grow(
-        cell: &mut MapCell,
+    /// Grows the cell as `zone` based on its `previous` state, where `grid`
+    /// is the previous state of the whole region.
+    pub fn grow(
+        &mut self,
         previous: &MapCell,
+        grid: &MapGrid,
+        zone: &dyn ZoneBehavior,
+        config: &Config,
         workers: i32,
         goods: i32,
     ) -> (i32, i32) {
-        // Early return if not enough workers
-        if workers < 2 {
+        if zone.is_pollution_sensitive() && Self::pollution_effect(self, previous, config) {
             return (workers, goods);
         }
 
-        let mut workers_used = workers;
-
-        if previous.population == 0 && previous.is_powerline_adjacent {
-            cell.population = 1;
-            cell.pollution = 1;
-            workers_used -= 2;
-        } else {
-            let mut target_adjacents;
-            let target_population;
-
-            match previous.population {
-                0 => {
-                    target_adjacents = 1;
-                    target_population = 1;
-                }
-                1 => {
-                    target_adjacents = 2;
-                    target_population = 1;
-                }
-                2 => {
-                    target_adjacents = 4;
-                    target_population = 2;
-                }
-                _ => return (workers, goods),
-            }
-
-            for cell_weak in &previous.neighbors {
-                if let Some(neighbor) = cell_weak.upgrade() {
-                    let neighbor = neighbor.borrow();
+        if previous.is_browned_out
+            || self.is_at_capacity
+            || (previous.population >= 1 && !previous.is_road_adjacent)
+        {
+            return (workers, goods);
+        }
 
-                    if neighbor.population >= target_population {
-                        target_adjacents -= 1;
-                    }
+        zone.grow(self, previous, grid, workers, goods)
+    }
 
-                    if target_adjacents == 0 {
-                        cell.population = previous.population + 1;
-                        cell.pollution = previous.pollution + 1;
-                        workers_used -= 2;
-                        break;
-                    }
-                }
-            }
-        }
+    pub fn neighbors<'a>(&self, grid: &'a MapGrid) -> impl Iterator<Item = &'a MapCell> + 'a {
+        let (x, y) = self.position;
+        grid.neighbors(x as usize, y as usize)
+    }
 
-        (workers_used, goods) // Note: goods is unchanged for industrial
+    pub fn count_adjacent_population(&self, grid: &MapGrid) -> u32 {
+        let (x, y) = self.position;
+        grid.count_adjacent_population(x as usize, y as usize)
     }
 
-    pub fn count_adjacent_population(&self) -> u32 {
-        let mut total = 0;
-        for neighbor_weak in &self.neighbors {
-            if let Some(neighbor) = neighbor_weak.upgrade() {
-                let neighbor = neighbor.borrow();
-                total += neighbor.population;
-            }
+    /// Applies the configured pollution limits to a pollution sensitive cell.
+    /// Returns whether the pollution keeps the cell from growing this time
+    /// step, either because it declined or because it reached the growth
+    /// limit.
+    ///
+    /// With `Decline Delay` set, the decline limit puts the cell in distress
+    /// instead, and it declines through [`MapCell::decline`].
+    fn pollution_effect(cell: &mut MapCell, previous: &MapCell, config: &Config) -> bool {
+        let reached = |limit: Option<u32>| limit.is_some_and(|limit| previous.pollution >= limit);
+
+        if config.decline_delay.is_none()
+            && reached(config.pollution_decline_limit)
+            && previous.population > 0
+        {
+            cell.population = previous.population - 1;
+            return true;
         }
-        total
+
+        reached(config.pollution_growth_limit)
     }
 }
 
 impl Display for MapCell {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        if self.population == 0 {
+        if self.is_abandoned {
+            write!(f, "{}", ABANDONED_SYMBOL)
+        } else if self.population == 0 {
             write!(f, "{}", self.cell_type.as_char())
         } else {
             write!(f, "{}", self.population)
@@ -229,60 +191,62 @@ impl Display for MapCell {
     }
 }
 
-impl PartialEq for MapCell {
-    fn eq(&self, other: &Self) -> bool {
-        self.position == other.position
-            && self.cell_type == other.cell_type
-            && self.population == other.population
-            && self.pollution == other.pollution
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Steps a constantly distressed cell and returns its population and
+    /// abandonment after every time step.
+    fn decline_in_distress(cell: MapCell, config: &Config, steps: usize) -> Vec<(u32, bool)> {
+        let mut current = cell;
+        (0..steps)
+            .map(|_| {
+                let previous = current;
+                current.decline(&previous, true, config);
+                (current.population, current.is_abandoned)
+            })
+            .collect()
     }
-}
 
-impl Eq for MapCell {}
-
-impl PartialOrd for MapCell {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    #[test]
+    fn abandons_after_being_empty_for_the_abandonment_delay() {
+        let mut config = Config::new("region.csv", 10, 1);
+        config.decline_delay = Some(2);
+        config.abandonment_delay = Some(1);
+        let mut cell = MapCell::new((0, 0), CellType::Residential('R'));
+        cell.population = 3;
+
+        assert_eq!(
+            decline_in_distress(cell, &config, 6),
+            [
+                (3, false),
+                (2, false),
+                (1, false),
+                (0, false),
+                (0, true),
+                (0, true)
+            ]
+        );
     }
-}
-
-impl Ord for MapCell {
-    fn cmp(&self, other: &Self) -> Ordering {
-        // Rule 1: Commercial zoned cells are prioritized over industrial zoned cells,
-        // and industrial cells over residential
-        // Compare zone types with priority C > I > R
-        match (self.cell_type.as_char(), other.cell_type.as_char()) {
-            ('C', 'C') | ('I', 'I') | ('R', 'R') => {} // Same type, continue to next rule
-            ('C', _) => return Ordering::Less,         // C has highest priority (should be first)
-            (_, 'C') => return Ordering::Greater,      // Other has C, so it gets priority
-            ('I', _) => return Ordering::Less,         // I has priority over R
-            (_, 'I') => return Ordering::Greater,      // Other has I, so it gets priority
-            _ => {}                                    // Continue for other cell types
-        }
-
-        // Rule 2: Larger population gets priority
-        if self.population != other.population {
-            return other.population.cmp(&self.population); // Reverse order: larger population first
-        }
-
-        // Rule 3: Greater adjacent population gets priority
-        let self_adj_pop = self.count_adjacent_population();
-        let other_adj_pop = other.count_adjacent_population();
-
-        if self_adj_pop != other_adj_pop {
-            return other_adj_pop.cmp(&self_adj_pop); // Reverse order: larger adjacent pop first
-        }
-
-        // Rules 4 & 5: Compare coordinates (smaller Y first, then smaller X)
-        let (self_x, self_y) = self.position;
-        let (other_x, other_y) = other.position;
-
-        // Smaller Y first
-        if self_y != other_y {
-            return self_y.cmp(&other_y);
-        }
 
-        // Then smaller X
-        self_x.cmp(&other_x)
+    #[test]
+    fn recovers_when_the_distress_ends() {
+        let mut config = Config::new("region.csv", 10, 1);
+        config.decline_delay = Some(1);
+        config.abandonment_delay = Some(0);
+        let mut cell = MapCell::new((0, 0), CellType::Residential('R'));
+        cell.population = 1;
+
+        let history = decline_in_distress(cell, &config, 2);
+        assert_eq!(history, [(0, true), (0, true)]);
+
+        let mut abandoned = cell;
+        abandoned.population = 0;
+        abandoned.is_abandoned = true;
+        abandoned.distress = 1;
+        let previous = abandoned;
+        assert!(!abandoned.decline(&previous, false, &config));
+        assert!(!abandoned.is_abandoned);
+        assert_eq!(abandoned.distress, 0);
     }
 }
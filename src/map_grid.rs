@@ -0,0 +1,108 @@
+use crate::map_cell::MapCell;
+
+/// Offsets of the 8 cells surrounding a cell.
+const NEIGHBOR_OFFSETS: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// A rectangular grid of cells stored row by row in a single contiguous
+/// vector. Neighbors are found from coordinates, so cells hold no references
+/// to each other and the whole grid can be copied cheaply.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MapGrid {
+    width: usize,
+    height: usize,
+    cells: Vec<MapCell>,
+}
+
+impl MapGrid {
+    /// Creates a grid from cells given in row order.
+    ///
+    /// Panics if the number of cells is not `width * height`.
+    pub fn new(width: usize, height: usize, cells: Vec<MapCell>) -> Self {
+        assert_eq!(
+            cells.len(),
+            width * height,
+            "a {}x{} grid needs {} cells",
+            width,
+            height,
+            width * height
+        );
+
+        MapGrid {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&MapCell> {
+        if x < self.width && y < self.height {
+            Some(&self.cells[self.index(x, y)])
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut MapCell> {
+        if x < self.width && y < self.height {
+            let index = self.index(x, y);
+            Some(&mut self.cells[index])
+        } else {
+            None
+        }
+    }
+
+    pub fn cells(&self) -> &[MapCell] {
+        &self.cells
+    }
+
+    pub fn cells_mut(&mut self) -> &mut [MapCell] {
+        &mut self.cells
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[MapCell]> {
+        // `chunks` panics on a zero chunk size, which an empty grid would give.
+        self.cells.chunks(self.width.max(1))
+    }
+
+    /// Indices of the up to 8 cells surrounding `(x, y)`. The iterator does
+    /// not borrow the grid, so the neighbors can be modified while iterating.
+    pub fn neighbor_indices(&self, x: usize, y: usize) -> impl Iterator<Item = usize> + use<> {
+        let (width, height) = (self.width, self.height);
+        NEIGHBOR_OFFSETS.iter().filter_map(move |&(dx, dy)| {
+            let nx = x.checked_add_signed(dx)?;
+            let ny = y.checked_add_signed(dy)?;
+            (nx < width && ny < height).then_some(ny * width + nx)
+        })
+    }
+
+    /// The up to 8 cells surrounding `(x, y)`.
+    pub fn neighbors(&self, x: usize, y: usize) -> impl Iterator<Item = &MapCell> + '_ {
+        self.neighbor_indices(x, y)
+            .map(move |index| &self.cells[index])
+    }
+
+    pub fn count_adjacent_population(&self, x: usize, y: usize) -> u32 {
+        self.neighbors(x, y).map(|cell| cell.population).sum()
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{
+    error::{Error, Result},
+    map::Map,
+    zone::ZoneRegistry,
+};
+use std::{fmt, fs};
+
+/// Statistics of the region at a single time step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metrics {
+    pub time_step: u32,
+    /// Population of every zone type, in the order of
+    /// [`ZoneRegistry::zones`].
+    pub populations: Vec<u32>,
+    pub available_workers: i32,
+    pub available_goods: i32,
+    pub total_pollution: i32,
+    /// Energized powerlines and plants, and zones supplied with power.
+    pub powered_cells: u32,
+}
+
+impl Metrics {
+    pub fn collect(time_step: u32, map: &Map) -> Self {
+        let grid = &map.current;
+        let populations = map
+            .zones
+            .zones()
+            .map(|zone| {
+                grid.cells()
+                    .iter()
+                    .filter(|cell| {
+                        map.zones
+                            .for_cell(cell)
+                            .is_some_and(|cell_zone| cell_zone.symbol() == zone.symbol())
+                    })
+                    .map(|cell| cell.population)
+                    .sum()
+            })
+            .collect();
+
+        Metrics {
+            time_step,
+            populations,
+            available_workers: Map::get_available_workers(grid, &map.zones),
+            available_goods: Map::get_available_goods(grid, &map.zones),
+            total_pollution: Map::total_pollution(grid),
+            powered_cells: grid.cells().iter().filter(|cell| cell.is_powered).count() as u32,
+        }
+    }
+}
+
+/// The name of the population column or field of the zone type called
+/// `name`, e.g. `residential_population`.
+pub(crate) fn population_column(name: &str) -> String {
+    format!("{}_population", name.to_lowercase().replace(' ', "_"))
+}
+
+/// Collects the [`Metrics`] of every time step of a simulation and writes
+/// them as CSV, one row per time step.
+#[derive(Debug, Clone)]
+pub struct MetricsRecorder {
+    /// Names of the zone types, labelling the population columns.
+    zone_names: Vec<String>,
+    rows: Vec<Metrics>,
+}
+
+impl MetricsRecorder {
+    pub fn new(zones: &ZoneRegistry) -> Self {
+        MetricsRecorder {
+            zone_names: zones.zones().map(|zone| zone.name().to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, time_step: u32, map: &Map) {
+        self.rows.push(Metrics::collect(time_step, map));
+    }
+
+    pub fn rows(&self) -> &[Metrics] {
+        &self.rows
+    }
+
+    pub fn save(&self, metrics_filename: &str) -> Result<()> {
+        fs::write(metrics_filename, self.to_string()).map_err(|source| Error::Io {
+            filename: metrics_filename.to_string(),
+            source,
+        })
+    }
+}
+
+/// Writes the recorded metrics as CSV with a header row. Population columns
+/// are named after their zone, e.g. `residential_population`.
+impl fmt::Display for MetricsRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "time_step")?;
+        for name in &self.zone_names {
+            write!(f, ",{}", population_column(name))?;
+        }
+        writeln!(
+            f,
+            ",available_workers,available_goods,total_pollution,powered_cells"
+        )?;
+
+        for row in &self.rows {
+            write!(f, "{}", row.time_step)?;
+            for population in &row.populations {
+                write!(f, ",{}", population)?;
+            }
+            writeln!(
+                f,
+                ",{},{},{},{}",
+                row.available_workers, row.available_goods, row.total_pollution, row.powered_cells
+            )?;
+        }
+
+        Ok(())
+    }
+}
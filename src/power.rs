@@ -0,0 +1,264 @@
+use crate::{
+    map_cell::{CellType, MapCell},
+    map_grid::MapGrid,
+};
+use std::collections::VecDeque;
+
+/// The power grid of a region, traced outwards from every power plant (`P`)
+/// through connected powerlines (`T` and `#`).
+///
+/// Every energized cell is fed by the plant closest to it along the lines,
+/// and a zone is powered by the plant feeding the nearest energized cell next
+/// to it. Powerlines that are not connected to any plant stay dead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PowerNetwork {
+    /// Positions of the power plants, in row order. Plants are identified by
+    /// their index in this list.
+    plants: Vec<(u32, u32)>,
+    /// The plant feeding each cell, by cell index: set for energized
+    /// powerlines and plants as well as for powered zones.
+    sources: Vec<Option<usize>>,
+    /// Power drawn from each plant during the last call to `supply`, plus
+    /// the units reserved since by zones that grew.
+    loads: Vec<u32>,
+    /// The capacity of every plant during the last call to `supply`.
+    capacity: Option<u32>,
+}
+
+impl PowerNetwork {
+    pub fn trace(grid: &MapGrid) -> Self {
+        let mut plants = Vec::new();
+        let mut sources = vec![None; grid.cells().len()];
+        let mut distances = vec![u32::MAX; grid.cells().len()];
+        let mut queue = VecDeque::new();
+
+        for (index, cell) in grid.cells().iter().enumerate() {
+            if is_plant(cell) {
+                sources[index] = Some(plants.len());
+                distances[index] = 0;
+                plants.push(cell.position);
+                queue.push_back(index);
+            }
+        }
+
+        // Breadth first from all plants at once, so each line ends up fed by
+        // its nearest plant.
+        while let Some(index) = queue.pop_front() {
+            let (x, y) = grid.cells()[index].position;
+            for neighbor in grid.neighbor_indices(x as usize, y as usize) {
+                if sources[neighbor].is_none() && is_conductor(&grid.cells()[neighbor]) {
+                    sources[neighbor] = sources[index];
+                    distances[neighbor] = distances[index] + 1;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        for (index, cell) in grid.cells().iter().enumerate() {
+            if !cell.cell_type.is_zone() {
+                continue;
+            }
+
+            let (x, y) = cell.position;
+            sources[index] = grid
+                .neighbor_indices(x as usize, y as usize)
+                .filter(|&neighbor| is_conductor(&grid.cells()[neighbor]))
+                .filter_map(|neighbor| Some((distances[neighbor], sources[neighbor]?)))
+                .min()
+                .map(|(_, plant)| plant);
+        }
+
+        let loads = vec![0; plants.len()];
+        PowerNetwork {
+            plants,
+            sources,
+            loads,
+            capacity: None,
+        }
+    }
+
+    /// Supplies the zones at `order`, in that order, from their plants and
+    /// marks the zones a plant has no capacity left for as browned out.
+    ///
+    /// A populated zone draws one unit per population. An unpopulated zone
+    /// draws nothing, but only stays powered while its plant has at least
+    /// one unit left for it to grow into.
+    pub fn supply(&mut self, grid: &mut MapGrid, order: &[usize], capacity: Option<u32>) {
+        self.loads.iter_mut().for_each(|load| *load = 0);
+        self.capacity = capacity;
+
+        for &index in order {
+            let Some(plant) = self.source(index) else {
+                continue;
+            };
+
+            let cell = &mut grid.cells_mut()[index];
+            let demand = cell.population.max(1);
+            let has_capacity =
+                capacity.is_none_or(|capacity| self.loads[plant] + demand <= capacity);
+
+            cell.is_powered = has_capacity;
+            cell.is_browned_out = !has_capacity;
+            if has_capacity {
+                self.loads[plant] += cell.population;
+            }
+        }
+    }
+
+    /// Whether the plant feeding the zone at `index` has a unit left for it
+    /// to grow into. Zones without a plant are not limited.
+    pub fn can_grow(&self, index: usize) -> bool {
+        match (self.source(index), self.capacity) {
+            (Some(plant), Some(capacity)) => self.loads[plant] < capacity,
+            _ => true,
+        }
+    }
+
+    /// Reserves the unit the zone at `index` grew into, so zones later in the
+    /// same time step cannot take its plant beyond capacity.
+    pub fn reserve(&mut self, index: usize) {
+        if let Some(plant) = self.source(index) {
+            self.loads[plant] += 1;
+        }
+    }
+
+    /// Power drawn from each plant during the last time step, including the
+    /// units reserved for growth, indexed like `plants`.
+    pub fn loads(&self) -> &[u32] {
+        &self.loads
+    }
+
+    pub fn plants(&self) -> &[(u32, u32)] {
+        &self.plants
+    }
+
+    /// The plant feeding the cell at `index`, if any.
+    pub fn source(&self, index: usize) -> Option<usize> {
+        self.sources.get(index).copied().flatten()
+    }
+
+    /// Whether the cell at `index` is a powerline or plant connected to a
+    /// plant, or a zone next to one.
+    pub fn is_powered(&self, index: usize) -> bool {
+        self.source(index).is_some()
+    }
+
+    /// Indices of the zones powered by `plant`.
+    pub fn zones_powered_by<'a>(
+        &'a self,
+        grid: &'a MapGrid,
+        plant: usize,
+    ) -> impl Iterator<Item = usize> + 'a {
+        grid.cells()
+            .iter()
+            .enumerate()
+            .filter(move |(index, cell)| {
+                cell.cell_type.is_zone() && self.source(*index) == Some(plant)
+            })
+            .map(|(index, _)| index)
+    }
+}
+
+fn is_plant(cell: &MapCell) -> bool {
+    cell.cell_type == CellType::Other('P')
+}
+
+fn is_conductor(cell: &MapCell) -> bool {
+    matches!(cell.cell_type, CellType::Other('T' | '#' | 'P'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, map::Map, zone::ZoneRegistry};
+
+    fn grid(rows: &[&str]) -> MapGrid {
+        let zones = ZoneRegistry::default();
+        let cells = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                let zones = &zones;
+                row.chars().enumerate().map(move |(x, symbol)| {
+                    MapCell::new((x as u32, y as u32), zones.cell_type(symbol))
+                })
+            })
+            .collect();
+
+        MapGrid::new(rows[0].len(), rows.len(), cells)
+    }
+
+    /// Population of the powered zones, which draw one unit each.
+    fn power_draw(map: &Map) -> u32 {
+        map.current
+            .cells()
+            .iter()
+            .filter(|cell| cell.is_powered && cell.cell_type.is_zone())
+            .map(|cell| cell.population)
+            .sum()
+    }
+
+    fn set_populations(grid: &mut MapGrid, populations: &[(usize, u32)]) {
+        for &(index, population) in populations {
+            grid.cells_mut()[index].population = population;
+        }
+    }
+
+    #[test]
+    fn feeds_zones_from_the_nearest_plant() {
+        let grid = grid(&["PTRTTTP", "       ", "R#     "]);
+        let power = PowerNetwork::trace(&grid);
+
+        assert_eq!(power.plants(), [(0, 0), (6, 0)]);
+        assert_eq!(power.source(2), Some(0));
+        assert_eq!(power.source(5), Some(1));
+        assert_eq!(power.zones_powered_by(&grid, 0).collect::<Vec<_>>(), [2]);
+        assert!(!power.is_powered(14));
+        assert!(!power.is_powered(15));
+    }
+
+    #[test]
+    fn reserves_units_for_growth() {
+        let mut grid = grid(&["RPR"]);
+        let mut power = PowerNetwork::trace(&grid);
+        set_populations(&mut grid, &[(0, 1), (2, 1)]);
+
+        power.supply(&mut grid, &[0, 2], Some(3));
+        assert_eq!(power.loads(), [2]);
+        assert!(power.can_grow(0));
+
+        power.reserve(0);
+        assert_eq!(power.loads(), [3]);
+        assert!(!power.can_grow(2));
+        assert!(grid.cells()[2].is_powered && !grid.cells()[2].is_browned_out);
+    }
+
+    #[test]
+    fn browns_out_zones_beyond_capacity() {
+        let mut grid = grid(&["RPR"]);
+        let mut power = PowerNetwork::trace(&grid);
+        set_populations(&mut grid, &[(0, 3), (2, 0)]);
+
+        power.supply(&mut grid, &[0, 2], Some(2));
+
+        assert!(grid.cells()[0].is_browned_out);
+        assert!(grid.cells()[2].is_powered);
+        assert_eq!(power.loads(), [0]);
+    }
+
+    #[test]
+    fn regions_grow_within_capacity() {
+        let mut config = Config::new("region.csv", 20, 1);
+        config.power_plant_capacity = Some(3);
+        let layout = "-,-,-,-,-\n-,R,T,R,-\n-,R,T,R,-\n-,-,P,-,-";
+        let mut map = Map::parse("region.csv", layout, &ZoneRegistry::default()).unwrap();
+
+        for _ in 0..config.time_limit {
+            map.update_previous();
+            map.step(&config);
+            assert!(power_draw(&map) <= 3);
+        }
+
+        assert_eq!(Map::get_population(&map.current, None), 3);
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{
+    map::Map,
+    map_cell::{CellType, MapCell},
+    map_grid::MapGrid,
+    road,
+};
+use std::{
+    env, fmt,
+    io::{self, IsTerminal},
+};
+
+/// When to print the region in color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    /// Only when stdout is a terminal and `NO_COLOR` is not set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    pub fn enabled(&self) -> bool {
+        match self {
+            ColorMode::Auto => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+/// A per-cell view of the region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layer {
+    /// Cell symbols, or the population of populated zones.
+    #[default]
+    Zoning,
+    /// Power plants (`P`), powered cells (`+`), browned out zones (`!`) and
+    /// unpowered zones and powerlines (`.`).
+    Power,
+    /// The pollution of every cell.
+    Pollution,
+    /// The population of every zone.
+    Population,
+    /// The [land value](Map::land_value) of every cell.
+    LandValue,
+    /// Roads, and the road district of every zone. Zones without road
+    /// access are shown as `x`.
+    Roads,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 6] = [
+        Layer::Zoning,
+        Layer::Power,
+        Layer::Pollution,
+        Layer::Population,
+        Layer::LandValue,
+        Layer::Roads,
+    ];
+
+    /// The name of the layer on the command line, e.g. `land-value`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::Zoning => "zoning",
+            Layer::Power => "power",
+            Layer::Pollution => "pollution",
+            Layer::Population => "population",
+            Layer::LandValue => "land-value",
+            Layer::Roads => "roads",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Layer> {
+        Self::ALL.into_iter().find(|layer| layer.name() == name)
+    }
+
+    /// The heading printed above the layer.
+    pub fn title(&self) -> &'static str {
+        match self {
+            Layer::Zoning => "Zoning",
+            Layer::Power => "Power",
+            Layer::Pollution => "Pollution",
+            Layer::Population => "Population",
+            Layer::LandValue => "Land value",
+            Layer::Roads => "Road districts",
+        }
+    }
+
+    fn cell_text(&self, map: &Map, cell: &MapCell) -> String {
+        let is_zone = map.zones.for_cell(cell).is_some();
+        let (x, y) = cell.position;
+        let index = map.current.index(x as usize, y as usize);
+
+        match self {
+            Layer::Zoning => map.glyph(cell),
+            Layer::Power if cell.cell_type == CellType::Other('P') => "P".to_string(),
+            Layer::Power if cell.is_browned_out => "!".to_string(),
+            Layer::Power if cell.is_powered => "+".to_string(),
+            Layer::Power if is_zone || matches!(cell.cell_type, CellType::Other('T' | '#')) => {
+                ".".to_string()
+            }
+            Layer::Pollution => cell.pollution.to_string(),
+            Layer::Population if is_zone => cell.population.to_string(),
+            Layer::LandValue => Map::land_value(&map.current, cell).to_string(),
+            Layer::Roads if road::is_road(cell) => cell.cell_type.as_char().to_string(),
+            Layer::Roads if is_zone && !cell.is_road_adjacent => "x".to_string(),
+            Layer::Roads => map
+                .roads
+                .district(index)
+                .map_or(String::new(), |district| district.to_string()),
+            _ => String::new(),
+        }
+    }
+}
+
+/// 256 color palette shades of each zone type, from the lowest population
+/// level to the highest.
+const RESIDENTIAL_SHADES: [u8; 5] = [22, 28, 34, 40, 46];
+const COMMERCIAL_SHADES: [u8; 5] = [25, 26, 27, 33, 39];
+const INDUSTRIAL_SHADES: [u8; 5] = [94, 136, 172, 178, 220];
+const CUSTOM_SHADES: [u8; 5] = [90, 127, 163, 164, 201];
+
+const ROAD_COLOR: u8 = 245;
+const POWERLINE_COLOR: u8 = 51;
+const PLANT_COLOR: u8 = 196;
+const ABANDONED_COLOR: u8 = 52;
+
+/// A [`Layer`] of the region, printed like [`Map`]'s `Display`. The zoning
+/// layer can be printed in color: zones are tinted by type and shaded by
+/// population, and roads, powerlines and power plants each get their own
+/// color.
+pub struct MapView<'a> {
+    map: &'a Map,
+    layer: Layer,
+    color: bool,
+}
+
+impl<'a> MapView<'a> {
+    pub fn new(map: &'a Map) -> Self {
+        MapView {
+            map,
+            layer: Layer::default(),
+            color: false,
+        }
+    }
+
+    pub fn with_layer(mut self, layer: Layer) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// The text of a cell in this view, padded to 3 characters and colored
+    /// if enabled.
+    pub fn cell_text(&self, cell: &MapCell) -> String {
+        let text = self.layer.cell_text(self.map, cell);
+        let color = self.color && self.layer == Layer::Zoning;
+
+        match Self::style(cell).filter(|_| color) {
+            Some(style) => format!("{}{:<3}\x1b[0m", style, text),
+            None => format!("{:<3}", text),
+        }
+    }
+
+    /// The ANSI escape sequence setting the color of a cell, or `None` to
+    /// leave it uncolored.
+    fn style(cell: &MapCell) -> Option<String> {
+        let color = palette_color(cell)?;
+
+        match cell.cell_type {
+            CellType::Other('P') => Some(format!("\x1b[1;38;5;{}m", color)),
+            CellType::Other(_) => Some(foreground(color)),
+            _ if cell.is_abandoned => Some(format!("\x1b[1;37;48;5;{}m", color)),
+            _ if cell.population == 0 => Some(foreground(color)),
+            _ => Some(format!("\x1b[1;30;48;5;{}m", color)),
+        }
+    }
+}
+
+/// The 256 color palette color of a cell, or `None` for cells without one.
+/// Zones are tinted by type and shaded by population, and abandoned zones,
+/// roads, powerlines and power plants each get their own color.
+pub(crate) fn palette_color(cell: &MapCell) -> Option<u8> {
+    let shades = match cell.cell_type {
+        CellType::Residential(_) => RESIDENTIAL_SHADES,
+        CellType::Commercial(_) => COMMERCIAL_SHADES,
+        CellType::Industrial(_) => INDUSTRIAL_SHADES,
+        CellType::Custom(_) => CUSTOM_SHADES,
+        CellType::Other('-') => return Some(ROAD_COLOR),
+        CellType::Other('T' | '#') => return Some(POWERLINE_COLOR),
+        CellType::Other('P') => return Some(PLANT_COLOR),
+        CellType::Other(_) => return None,
+    };
+
+    if cell.is_abandoned {
+        return Some(ABANDONED_COLOR);
+    }
+
+    let level = (cell.population as usize).clamp(1, shades.len());
+    Some(shades[level - 1])
+}
+
+/// The red, green and blue components of a 256 color palette color, as
+/// xterm draws them.
+pub(crate) fn rgb(color: u8) -> (u8, u8, u8) {
+    const SYSTEM: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (128, 0, 0),
+        (0, 128, 0),
+        (128, 128, 0),
+        (0, 0, 128),
+        (128, 0, 128),
+        (0, 128, 128),
+        (192, 192, 192),
+        (128, 128, 128),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (0, 0, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    // The 6x6x6 color cube uses these intensities per component.
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+    match color {
+        0..=15 => SYSTEM[color as usize],
+        16..=231 => {
+            let index = (color - 16) as usize;
+            (LEVELS[index / 36], LEVELS[index / 6 % 6], LEVELS[index % 6])
+        }
+        _ => {
+            let gray = 8 + (color - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+fn foreground(color: u8) -> String {
+    format!("\x1b[38;5;{}m", color)
+}
+
+impl fmt::Display for MapView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_grid(f, &self.map.current, |cell| self.cell_text(cell))
+    }
+}
+
+/// Writes a grid inside ASCII borders, giving every cell 4 characters: a
+/// space followed by the 3 character wide text from `cell_text`.
+pub fn write_grid(
+    f: &mut fmt::Formatter<'_>,
+    grid: &MapGrid,
+    cell_text: impl Fn(&MapCell) -> String,
+) -> fmt::Result {
+    let width = grid.width();
+
+    // Print top border
+    for _ in 0..width {
+        write!(f, "----")?;
+    }
+    writeln!(f, "--")?;
+
+    // Print each row with cells
+    for row in grid.rows() {
+        write!(f, "|")?; // Left border
+        for cell in row {
+            write!(f, " {}", cell_text(cell))?;
+        }
+        writeln!(f, "|")?; // Right border without extra space
+    }
+
+    // Print bottom border
+    for _ in 0..width {
+        write!(f, "----")?;
+    }
+    write!(f, "--")
+}
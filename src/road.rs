@@ -0,0 +1,177 @@
+use crate::{
+    map_cell::{CellType, MapCell},
+    map_grid::MapGrid,
+};
+
+/// The road network of a region, split into districts: groups of zones that
+/// can reach each other over roads (`-` and `#`).
+///
+/// Roads are connected to all 8 surrounding road cells and zones join the
+/// district of every road next to them, so a zone touching two separate
+/// roads links them into one district. Zones without road access form a
+/// district of their own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoadNetwork {
+    /// The district of each cell, by cell index. Only set for zones.
+    districts: Vec<Option<usize>>,
+    district_count: usize,
+}
+
+impl RoadNetwork {
+    pub fn trace(grid: &MapGrid) -> Self {
+        let mut parents: Vec<usize> = (0..grid.cells().len()).collect();
+
+        for (index, cell) in grid.cells().iter().enumerate() {
+            if !is_road(cell) {
+                continue;
+            }
+
+            let (x, y) = cell.position;
+            for neighbor in grid.neighbor_indices(x as usize, y as usize) {
+                let neighbor_cell = &grid.cells()[neighbor];
+                if is_road(neighbor_cell) || neighbor_cell.cell_type.is_zone() {
+                    union(&mut parents, index, neighbor);
+                }
+            }
+        }
+
+        // Number the districts in order of their first zone.
+        let mut district_ids = vec![None; grid.cells().len()];
+        let mut districts = vec![None; grid.cells().len()];
+        let mut district_count = 0;
+        for (index, cell) in grid.cells().iter().enumerate() {
+            if !cell.cell_type.is_zone() {
+                continue;
+            }
+
+            let root = find(&mut parents, index);
+            let district = *district_ids[root].get_or_insert_with(|| {
+                district_count += 1;
+                district_count - 1
+            });
+            districts[index] = Some(district);
+        }
+
+        RoadNetwork {
+            districts,
+            district_count,
+        }
+    }
+
+    pub fn district_count(&self) -> usize {
+        self.district_count
+    }
+
+    /// The district of the zone at `index`, or `None` if the cell is not a
+    /// zone.
+    pub fn district(&self, index: usize) -> Option<usize> {
+        self.districts.get(index).copied().flatten()
+    }
+}
+
+/// Whether workers and goods can travel over the cell.
+pub fn is_road(cell: &MapCell) -> bool {
+    matches!(cell.cell_type, CellType::Other('-' | '#'))
+}
+
+fn find(parents: &mut [usize], index: usize) -> usize {
+    let mut root = index;
+    while parents[root] != root {
+        root = parents[root];
+    }
+
+    // Point the whole path at the root to keep later lookups short.
+    let mut current = index;
+    while parents[current] != root {
+        let next = parents[current];
+        parents[current] = root;
+        current = next;
+    }
+
+    root
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let root_a = find(parents, a);
+    let root_b = find(parents, b);
+    if root_a != root_b {
+        parents[root_b] = root_a;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zone::ZoneRegistry;
+
+    fn grid(rows: &[&str]) -> MapGrid {
+        let zones = ZoneRegistry::default();
+        let cells = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                let zones = &zones;
+                row.chars().enumerate().map(move |(x, symbol)| {
+                    MapCell::new((x as u32, y as u32), zones.cell_type(symbol))
+                })
+            })
+            .collect();
+
+        MapGrid::new(rows[0].len(), rows.len(), cells)
+    }
+
+    fn districts(grid: &MapGrid) -> Vec<Option<usize>> {
+        let roads = RoadNetwork::trace(grid);
+        (0..grid.cells().len())
+            .map(|index| roads.district(index))
+            .collect()
+    }
+
+    #[test]
+    fn splits_zones_by_connected_roads() {
+        let grid = grid(&["R-C I", "    -", "R   R"]);
+        let roads = RoadNetwork::trace(&grid);
+
+        assert_eq!(roads.district_count(), 3);
+        assert_eq!(
+            districts(&grid),
+            [
+                Some(0),
+                None,
+                Some(0),
+                None,
+                Some(1),
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(2),
+                None,
+                None,
+                None,
+                Some(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn connects_roads_diagonally_and_through_powerlines() {
+        let grid = grid(&["R-   ", "  #  ", "   -C"]);
+        let roads = RoadNetwork::trace(&grid);
+
+        assert_eq!(roads.district_count(), 1);
+        assert_eq!(roads.district(0), roads.district(14));
+    }
+
+    #[test]
+    fn zones_link_the_roads_they_touch() {
+        let grid = grid(&["I-R-C", "     ", "R-  C"]);
+        let roads = RoadNetwork::trace(&grid);
+
+        assert_eq!(roads.district_count(), 3);
+        assert_eq!(roads.district(0), roads.district(4));
+        assert_ne!(roads.district(10), roads.district(0));
+        assert_ne!(roads.district(14), roads.district(10));
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{
+    config::Setting,
+    error::{Error, Result},
+};
+use std::fs;
+
+/// The condition for a zone to grow from one population level to the next:
+/// at least `neighbors` adjacent cells with a population of at least
+/// `neighbor_population`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GrowthThreshold {
+    pub neighbors: u32,
+    pub neighbor_population: u32,
+}
+
+/// How a single zone type grows and which resources it uses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZoneRules {
+    /// The threshold to grow past each population level, starting at 0. The
+    /// zone's maximum population is the number of thresholds. Powered zones
+    /// grow past population 0 regardless of the first threshold.
+    pub thresholds: Vec<GrowthThreshold>,
+    /// Workers provided (positive) or employed (negative) per population.
+    /// A zone employing workers needs that many available to grow.
+    pub workers_per_population: i32,
+    /// Goods produced (positive) or sold (negative) per population. A zone
+    /// selling goods needs that many available to grow.
+    pub goods_per_population: i32,
+    /// Pollution emitted per population.
+    pub pollution_per_population: u32,
+}
+
+impl ZoneRules {
+    pub fn max_population(&self) -> u32 {
+        self.thresholds.len() as u32
+    }
+
+    /// The threshold to grow past `population`, or `None` at the maximum.
+    pub fn threshold(&self, population: u32) -> Option<GrowthThreshold> {
+        self.thresholds.get(population as usize).copied()
+    }
+
+    /// Workers needed to grow by one population.
+    pub fn worker_cost(&self) -> i32 {
+        (-self.workers_per_population).max(0)
+    }
+
+    /// Goods needed to grow by one population.
+    pub fn goods_cost(&self) -> i32 {
+        (-self.goods_per_population).max(0)
+    }
+}
+
+/// The growth rules of all zone types. The defaults are the classic rules;
+/// a rules file only needs to list the settings it changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrowthRules {
+    pub residential: ZoneRules,
+    pub commercial: ZoneRules,
+    pub industrial: ZoneRules,
+}
+
+impl Default for GrowthRules {
+    fn default() -> Self {
+        let thresholds = |thresholds: &[(u32, u32)]| {
+            thresholds
+                .iter()
+                .map(|&(neighbors, neighbor_population)| GrowthThreshold {
+                    neighbors,
+                    neighbor_population,
+                })
+                .collect()
+        };
+
+        GrowthRules {
+            residential: ZoneRules {
+                thresholds: thresholds(&[(1, 1), (2, 1), (4, 2), (6, 3), (8, 4)]),
+                workers_per_population: 1,
+                goods_per_population: 0,
+                pollution_per_population: 0,
+            },
+            commercial: ZoneRules {
+                thresholds: thresholds(&[(1, 1), (2, 1)]),
+                workers_per_population: -1,
+                goods_per_population: -1,
+                pollution_per_population: 0,
+            },
+            industrial: ZoneRules {
+                thresholds: thresholds(&[(1, 1), (2, 1), (4, 2)]),
+                workers_per_population: -2,
+                goods_per_population: 1,
+                pollution_per_population: 1,
+            },
+        }
+    }
+}
+
+impl GrowthRules {
+    pub fn from_file(rules_filename: &str) -> Result<Self> {
+        let contents = fs::read_to_string(rules_filename).map_err(|source| Error::Io {
+            filename: rules_filename.to_string(),
+            source,
+        })?;
+
+        Self::parse(rules_filename, &contents)
+    }
+
+    /// Parses a rules file of `<Zone> <Setting>:Value` lines, for example
+    /// `Industrial Growth:1x1,2x1,4x2` or `Commercial Workers:-1`. Blank
+    /// lines and lines starting with `#` are skipped, unknown keys are
+    /// ignored.
+    pub fn parse(rules_filename: &str, contents: &str) -> Result<Self> {
+        let mut rules = GrowthRules::default();
+
+        for setting in Setting::parse_all(rules_filename, contents) {
+            let setting = setting?;
+            let Some((zone, key)) = setting.key.split_once(' ') else {
+                continue;
+            };
+
+            let zone = match zone {
+                "Residential" => &mut rules.residential,
+                "Commercial" => &mut rules.commercial,
+                "Industrial" => &mut rules.industrial,
+                _ => continue,
+            };
+
+            match key {
+                "Growth" => zone.thresholds = Self::parse_thresholds(&setting)?,
+                "Workers" => zone.workers_per_population = setting.integer()?,
+                "Goods" => zone.goods_per_population = setting.integer()?,
+                "Pollution" => zone.pollution_per_population = setting.whole()?,
+                _ => {}
+            }
+        }
+
+        Ok(rules)
+    }
+
+    /// Parses a comma separated list of `NEIGHBORSxPOPULATION` thresholds.
+    fn parse_thresholds(setting: &Setting) -> Result<Vec<GrowthThreshold>> {
+        let invalid = || setting.invalid("thresholds like 1x1,2x1,4x2");
+
+        setting
+            .value
+            .split(',')
+            .map(|threshold| {
+                let (neighbors, neighbor_population) =
+                    threshold.trim().split_once('x').ok_or_else(invalid)?;
+
+                Ok(GrowthThreshold {
+                    neighbors: neighbors.trim().parse().map_err(|_| invalid())?,
+                    neighbor_population: neighbor_population
+                        .trim()
+                        .parse()
+                        .map_err(|_| invalid())?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_only_the_listed_settings() {
+        let contents = "# Faster industry\nIndustrial Growth: 1x1, 1x1\nCommercial Workers:-2\nPark Growth:1x1\n";
+        let rules = GrowthRules::parse("rules.txt", contents).unwrap();
+        let defaults = GrowthRules::default();
+
+        assert_eq!(rules.industrial.max_population(), 2);
+        assert_eq!(
+            rules.industrial.threshold(1),
+            Some(GrowthThreshold {
+                neighbors: 1,
+                neighbor_population: 1
+            })
+        );
+        assert_eq!(rules.industrial.goods_per_population, 1);
+        assert_eq!(rules.commercial.worker_cost(), 2);
+        assert_eq!(rules.commercial.thresholds, defaults.commercial.thresholds);
+        assert_eq!(rules.residential, defaults.residential);
+    }
+
+    #[test]
+    fn points_invalid_thresholds_at_the_value() {
+        let error = GrowthRules::parse("rules.txt", "\nResidential Growth:1x1,2-1").unwrap_err();
+
+        assert!(matches!(
+            error,
+            Error::InvalidValue { line: 2, column: 20, ref key, .. } if key == "Residential Growth"
+        ));
+    }
+}
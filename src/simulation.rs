@@ -1,75 +1,462 @@
-use crate::{config::Config, map::Map, map_cell::CellType};
+use crate::{
+    config::Config,
+    image::{FrameRecorder, ImageRecorder},
+    json::JsonObject,
+    map::{Area, Map},
+    map_cell::MapCell,
+    map_grid::MapGrid,
+    metrics::{self, Metrics, MetricsRecorder},
+    render::{Layer, MapView},
+};
+use std::{
+    collections::{hash_map::DefaultHasher, VecDeque},
+    fmt,
+    hash::{Hash, Hasher},
+    io,
+};
 
-pub struct Simulation<'a> {
-    config: &'a Config,
-    map: &'a mut Map,
+/// The result of advancing the simulation by a single time step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// The region changed and the time limit has not been reached yet.
+    Continued,
+    /// The configured `Time Limit` has been reached.
+    ReachedTimeLimit,
+    /// The region did not change during the last time step.
+    ReachedSteadyState,
+    /// The region returned to a state it was in `period` time steps ago,
+    /// at time step `start`, and will keep repeating the same states.
+    ReachedCycle { period: u32, start: u32 },
+}
+
+impl StepOutcome {
+    pub fn is_finished(&self) -> bool {
+        *self != StepOutcome::Continued
+    }
+
+    /// A short identifier of the outcome for machine-readable output.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            StepOutcome::Continued => "continued",
+            StepOutcome::ReachedTimeLimit => "time_limit",
+            StepOutcome::ReachedSteadyState => "steady_state",
+            StepOutcome::ReachedCycle { .. } => "cycle",
+        }
+    }
+}
+
+impl fmt::Display for StepOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepOutcome::Continued => write!(f, "The simulation is still running"),
+            StepOutcome::ReachedTimeLimit => write!(f, "Reached the time limit"),
+            StepOutcome::ReachedSteadyState => write!(f, "The region stopped changing"),
+            StepOutcome::ReachedCycle { period, start } => write!(
+                f,
+                "The region repeats every {} time step(s), starting at time step {}",
+                period, start
+            ),
+        }
+    }
+}
+
+/// Which reports `Simulation::start` prints while running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMode {
+    /// The initial state, every refresh state and the final report.
+    #[default]
+    Full,
+    /// Only the final report.
+    Final,
+    /// The initial state, every refresh state and the final report as JSON
+    /// Lines: one JSON object per line, told apart by their `record` field.
+    Json,
+}
+
+pub struct Simulation {
+    config: Config,
+    map: Map,
     time_step: u32,
     state: u32,
+    /// The time step the simulation started or was resumed at.
+    start_time_step: u32,
+    /// The last time step that changed the region.
+    last_change: u32,
+    outcome: StepOutcome,
+    output_mode: OutputMode,
+    metrics: Option<MetricsRecorder>,
+    images: Option<ImageRecorder>,
+    frames: Option<FrameRecorder>,
+    /// Whether the text reports print the region in color.
+    color: bool,
+    /// The layers of the region the text reports print.
+    layers: Vec<Layer>,
+    /// The current state and the states of the `Cycle Detection Window`
+    /// time steps before it, oldest first, each with its hash and time step.
+    history: VecDeque<(u64, u32, MapGrid)>,
 }
 
-impl<'a> Simulation<'a> {
-    pub fn new(config: &'a Config, map: &'a mut Map) -> Self {
-        Simulation {
+impl Simulation {
+    pub fn new(config: Config, map: Map) -> Self {
+        Self::resume(config, map, 0, 0, 0)
+    }
+
+    /// Continues a simulation at `time_step`, after `state` refresh states
+    /// have been reached and with the region last changed at `last_change`.
+    ///
+    /// A simulation resumed at its time limit, or after its region stopped
+    /// changing, is already finished and does not step any further. Cycle
+    /// detection starts over with the resumed state.
+    pub fn resume(config: Config, map: Map, time_step: u32, state: u32, last_change: u32) -> Self {
+        let mut simulation = Simulation {
             config,
             map,
-            time_step: 0,
-            state: 0,
+            time_step,
+            state,
+            start_time_step: time_step,
+            last_change,
+            outcome: StepOutcome::Continued,
+            output_mode: OutputMode::default(),
+            metrics: None,
+            images: None,
+            frames: None,
+            color: false,
+            layers: vec![Layer::Zoning],
+            history: VecDeque::new(),
+        };
+        simulation.remember_state();
+        if last_change < time_step {
+            simulation.outcome = StepOutcome::ReachedSteadyState;
+        } else if time_step >= simulation.config.time_limit {
+            simulation.outcome = StepOutcome::ReachedTimeLimit;
         }
+        simulation
+    }
+
+    pub fn with_output_mode(mut self, output_mode: OutputMode) -> Self {
+        self.output_mode = output_mode;
+        self
+    }
+
+    /// Prints the region in color in the text reports.
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Prints these layers of the region in the text reports, in order,
+    /// instead of only the zoning.
+    pub fn with_layers(mut self, layers: Vec<Layer>) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Records the [`Metrics`](crate::metrics::Metrics) of the current
+    /// state and of every following time step.
+    pub fn with_metrics(mut self) -> Self {
+        let mut metrics = MetricsRecorder::new(&self.map.zones);
+        metrics.record(self.time_step, &self.map);
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Records images of the current state, every following refresh state
+    /// and the final state with `images`.
+    pub fn with_images(mut self, mut images: ImageRecorder) -> Self {
+        images.record_initial(&self.map);
+        self.images = Some(images);
+        self
+    }
+
+    /// Records a frame of the current state, every following refresh state
+    /// and the final state with `frames`.
+    pub fn with_frames(mut self, mut frames: FrameRecorder) -> Self {
+        frames.record(self.time_step, &self.map);
+        self.frames = Some(frames);
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn map(&self) -> &Map {
+        &self.map
+    }
+
+    pub fn time_step(&self) -> u32 {
+        self.time_step
+    }
+
+    /// The number of refresh states reached so far.
+    pub fn state(&self) -> u32 {
+        self.state
+    }
+
+    /// The outcome of the last time step. Once the simulation has finished
+    /// this is the reason it stopped.
+    pub fn outcome(&self) -> StepOutcome {
+        self.outcome
+    }
+
+    /// The last time step that changed the region, or the time step the
+    /// simulation started at if none did.
+    pub fn last_change(&self) -> u32 {
+        self.last_change
+    }
+
+    /// The number of time steps simulated since the simulation started or
+    /// was resumed.
+    pub fn steps_simulated(&self) -> u32 {
+        self.time_step - self.start_time_step
+    }
+
+    /// The recorded metrics, if enabled with `with_metrics`.
+    pub fn metrics(&self) -> Option<&MetricsRecorder> {
+        self.metrics.as_ref()
+    }
+
+    /// The recorded images, if enabled with `with_images`.
+    pub fn images(&self) -> Option<&ImageRecorder> {
+        self.images.as_ref()
+    }
+
+    /// The recorded frames, if enabled with `with_frames`.
+    pub fn frames(&self) -> Option<&FrameRecorder> {
+        self.frames.as_ref()
     }
 
     pub fn start(&mut self) {
-        println!("\nINITIAL REGION STATE");
-        println!("{}\n", self.map);
-        self.next();
+        match self.output_mode {
+            OutputMode::Full => {
+                println!("\nINITIAL REGION STATE");
+                println!("{}\n", self.views());
+            }
+            OutputMode::Json => println!("{}", self.state_json("initial")),
+            OutputMode::Final => {}
+        }
+
+        while !self.outcome.is_finished() {
+            self.step();
+
+            if self.time_step.is_multiple_of(self.config.refresh_rate) {
+                match self.output_mode {
+                    OutputMode::Full => self.print_current_state(),
+                    OutputMode::Json => println!("{}", self.state_json("state")),
+                    OutputMode::Final => {}
+                }
+            }
+        }
+
+        self.end();
     }
 
-    fn next(&mut self) {
+    /// Advances the region by one time step. Once the simulation has
+    /// finished, further calls leave the map untouched and keep returning
+    /// the outcome that ended it.
+    pub fn step(&mut self) -> StepOutcome {
+        if self.outcome.is_finished() {
+            return self.outcome;
+        }
+
         self.map.update_previous();
-        Map::track_adjacency(&self.map.previous.as_ref().unwrap());
         self.time_step += 1;
-        self.map.step();
-
-        if self.time_step % self.config.refresh_rate == 0 {
+        if self.time_step.is_multiple_of(self.config.refresh_rate) {
             self.state += 1;
-            self.print_current_state();
+        }
+        self.map.step(&self.config);
+        if self.map.current != self.map.previous {
+            self.last_change = self.time_step;
+        }
+        if let Some(metrics) = &mut self.metrics {
+            metrics.record(self.time_step, &self.map);
         }
 
-        if self.should_simulation_end() {
-            self.map.spread_pollution();
-            self.end();
-        } else {
-            self.next();
+        self.outcome = self.check_outcome();
+        if self.time_step.is_multiple_of(self.config.refresh_rate) {
+            if let Some(images) = &mut self.images {
+                images.record_state(self.state, &self.map);
+            }
+            if let Some(frames) = &mut self.frames {
+                frames.record(self.time_step, &self.map);
+            }
+        }
+        if self.outcome.is_finished() {
+            self.finish_recording();
         }
+        self.outcome
     }
 
-    fn end(&self) {
+    /// Records the current state as the final image and frame. `step` does
+    /// this once the simulation finishes; a simulation stopped before that,
+    /// like a quit interactive run, needs it before its images and frames
+    /// are saved.
+    pub fn finish_recording(&mut self) {
+        if let Some(images) = &mut self.images {
+            images.record_final(&self.map);
+        }
+        if let Some(frames) = &mut self.frames {
+            frames.record(self.time_step, &self.map);
+        }
+    }
+
+    /// Prints the final report in the output mode. `start` prints it once
+    /// the simulation finishes.
+    pub fn end(&self) {
+        if self.output_mode == OutputMode::Json {
+            println!("{}", self.final_json());
+            return;
+        }
+
         println!("\nFINAL REGION STATE");
-        println!("{}\n", self.map);
+        println!("{}\n", self.views());
+        println!("Termination reason: {}", self.outcome);
+        println!("Time steps simulated: {}", self.steps_simulated());
+        println!("Region last changed at time step: {}\n", self.last_change);
         println!(
             "Available Workers: {}",
-            Map::get_available_workers(&self.map.current)
+            Map::get_available_workers(&self.map.current, &self.map.zones)
         );
         println!(
             "Available Goods: {}",
-            Map::get_available_goods(&self.map.current)
+            Map::get_available_goods(&self.map.current, &self.map.zones)
         );
+        let stats = Map::get_area_stats(&self.map.current, &self.map.zones, &self.map.area());
+        println!("Total Population: {}", stats.total_population());
+        for (name, population) in &stats.populations {
+            println!("{} population: {}", name, population);
+        }
+        println!();
+        if let Some(capacity) = self.config.power_plant_capacity {
+            for (plant, load) in self.map.power.plants().iter().zip(self.map.power.loads()) {
+                println!(
+                    "Power plant at ({}, {}): {}/{} used",
+                    plant.0, plant.1, load, capacity
+                );
+            }
+            println!(
+                "Browned out zones: {}\n",
+                Map::get_browned_out_zones(&self.map.current)
+            );
+        }
+        if self.config.abandonment_delay.is_some() {
+            println!(
+                "Abandoned zones: {}\n",
+                Map::get_abandoned_zones(&self.map.current)
+            );
+        }
+        println!("Final pollution state: ");
+        Map::print_pollution(&self.map.current);
         println!(
-            "Total Population: {}",
-            Map::get_population(&self.map.current, None)
+            "Total pollution: {}\n",
+            Map::total_pollution(&self.map.current)
         );
+    }
+
+    /// Asks for an area on stdin and prints its analysis. Nothing is
+    /// printed if stdin ends before a valid area is entered.
+    pub fn analyze_area(&self) {
+        if let Some(area) = self.request_area() {
+            self.print_area_analysis(&area);
+        }
+    }
+
+    pub fn print_area_analysis(&self, area: &Area) {
+        let stats = Map::get_area_stats(&self.map.current, &self.map.zones, area);
+
+        if self.output_mode == OutputMode::Json {
+            let record = stats.populations.iter().fold(
+                JsonObject::new()
+                    .field("record", "area")
+                    .field("x1", &area.x1)
+                    .field("y1", &area.y1)
+                    .field("x2", &area.x2)
+                    .field("y2", &area.y2),
+                |record, (name, population)| {
+                    record.field(&metrics::population_column(name), population)
+                },
+            );
+            let record = record
+                .field("total_population", &stats.total_population())
+                .field("total_pollution", &stats.pollution);
+            println!("{}", record);
+            return;
+        }
+
         println!(
-            "Residential population: {}",
-            Map::get_population(&self.map.current, Some(&CellType::Residential('R')))
+            "\nThe regional analysis for ({}, {})-({}, {}):",
+            area.x1, area.y1, area.x2, area.y2
         );
+        for (name, population) in &stats.populations {
+            println!("{} population: {}", name, population);
+        }
+        println!("Total population: {}", stats.total_population());
+        println!("Total pollution: {}\n", stats.pollution);
+    }
+
+    fn request_area(&self) -> Option<Area> {
+        let grid = &self.map.current;
+        let max_x = grid.width().saturating_sub(1);
+        let max_y = grid.height().saturating_sub(1);
+
+        loop {
+            println!(
+                "Please enter the diagonal corners of the area you wish to have more information about. (MinX = 0, MinY = 0, MaxX = {}, MaxY = {})",
+                max_x, max_y
+            );
+            println!("Format: x1 y1 x2 y2");
+
+            let mut input = String::new();
+            if io::stdin()
+                .read_line(&mut input)
+                .expect("Failed to read line")
+                == 0
+            {
+                return None;
+            }
+
+            let coordinates: Vec<u32> = match input
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|part| !part.is_empty())
+                .map(|part| part.parse())
+                .collect()
+            {
+                Ok(coordinates) => coordinates,
+                Err(_) => {
+                    println!("Error. Coordinates must be non-negative whole numbers.");
+                    continue;
+                }
+            };
+
+            if coordinates.len() != 4 {
+                println!("Error. Expected exactly 4 coordinates.");
+                continue;
+            }
+
+            let area = Area::new(
+                (coordinates[0], coordinates[1]),
+                (coordinates[2], coordinates[3]),
+            );
+            match Map::validate_area(grid, &area) {
+                Ok(()) => return Some(area),
+                Err(e) => println!("Error: {}", e),
+            }
+        }
+    }
+
+    fn print_current_state(&self) {
+        println!("State: {}", self.state);
+        println!("Time Step: {}", self.time_step);
+        println!("{}", self.views());
         println!(
-            "Commercial population: {}",
-            Map::get_population(&self.map.current, Some(&CellType::Commercial('C')))
+            "Available Workers: {}",
+            Map::get_available_workers(&self.map.current, &self.map.zones)
         );
         println!(
-            "Industrial population: {}\n",
-            Map::get_population(&self.map.current, Some(&CellType::Industrial('I')))
+            "Available Goods: {}",
+            Map::get_available_goods(&self.map.current, &self.map.zones)
         );
-        println!("Final pollution state: ");
+        println!("Pollution: ");
         Map::print_pollution(&self.map.current);
         println!(
             "Total pollution: {}\n",
@@ -77,22 +464,246 @@ impl<'a> Simulation<'a> {
         );
     }
 
-    fn print_current_state(&self) {
-        println!("State: {}", self.state);
-        println!("Time Step: {}", self.time_step);
-        println!("{}", self.map);
-        println!(
-            "Available Workers: {}",
-            Map::get_available_workers(&self.map.current)
+    /// The selected layers of the region, every layer but the zoning under
+    /// a heading.
+    fn views(&self) -> String {
+        self.layers
+            .iter()
+            .map(|&layer| {
+                let view = MapView::new(&self.map)
+                    .with_layer(layer)
+                    .with_color(self.color);
+                match layer {
+                    Layer::Zoning => view.to_string(),
+                    layer => format!("{}:\n{}", layer.title(), view),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// The current state as a JSON record of the given kind, with the map as
+    /// rows of printed cells and the population and pollution of every cell.
+    fn state_json(&self, record: &str) -> JsonObject {
+        let grid = &self.map.current;
+        let rows = |value: fn(&MapCell) -> u32| -> Vec<Vec<u32>> {
+            grid.rows()
+                .map(|row| row.iter().map(value).collect())
+                .collect()
+        };
+        let glyphs: Vec<Vec<String>> = grid
+            .rows()
+            .map(|row| row.iter().map(|cell| self.map.glyph(cell)).collect())
+            .collect();
+
+        JsonObject::new()
+            .field("record", record)
+            .field("state", &self.state)
+            .field("time_step", &self.time_step)
+            .field(
+                "available_workers",
+                &Map::get_available_workers(grid, &self.map.zones),
+            )
+            .field(
+                "available_goods",
+                &Map::get_available_goods(grid, &self.map.zones),
+            )
+            .field("total_pollution", &Map::total_pollution(grid))
+            .field("grid", &glyphs)
+            .field("population", &rows(|cell| cell.population))
+            .field("pollution", &rows(|cell| cell.pollution))
+    }
+
+    /// The final report as a JSON record, with the population of every zone
+    /// type and the power and abandonment statistics when enabled.
+    fn final_json(&self) -> JsonObject {
+        let grid = &self.map.current;
+        let metrics = Metrics::collect(self.time_step, &self.map);
+        let populations = self
+            .map
+            .zones
+            .zones()
+            .zip(&metrics.populations)
+            .fold(JsonObject::new(), |populations, (zone, population)| {
+                populations.field(zone.name(), population)
+            });
+
+        let mut record = self
+            .state_json("final")
+            .field("termination_reason", self.outcome.reason())
+            .field("steps_simulated", &self.steps_simulated())
+            .field("last_change", &self.last_change)
+            .field("total_population", &Map::get_population(grid, None))
+            .field("populations", &populations);
+
+        if let Some(capacity) = self.config.power_plant_capacity {
+            let plants: Vec<JsonObject> = self
+                .map
+                .power
+                .plants()
+                .iter()
+                .zip(self.map.power.loads())
+                .map(|(&(x, y), load)| {
+                    JsonObject::new()
+                        .field("x", &x)
+                        .field("y", &y)
+                        .field("load", load)
+                        .field("capacity", &capacity)
+                })
+                .collect();
+            record = record
+                .field("power_plants", &plants)
+                .field("browned_out_zones", &Map::get_browned_out_zones(grid));
+        }
+        if self.config.abandonment_delay.is_some() {
+            record = record.field("abandoned_zones", &Map::get_abandoned_zones(grid));
+        }
+        if let StepOutcome::ReachedCycle { period, start } = self.outcome {
+            let cycle = JsonObject::new()
+                .field("period", &period)
+                .field("start", &start);
+            record = record.field("cycle", &cycle);
+        }
+
+        record
+    }
+
+    /// Adds the current state to the cycle detection history, dropping the
+    /// states that fell out of the window.
+    fn remember_state(&mut self) {
+        let window = self.config.cycle_detection_window as usize;
+        if window == 0 {
+            return;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        self.map.current.hash(&mut hasher);
+        self.history
+            .push_back((hasher.finish(), self.time_step, self.map.current.clone()));
+        while self.history.len() > window + 1 {
+            self.history.pop_front();
+        }
+    }
+
+    /// The time step the current state was last seen at, if it is still in
+    /// the cycle detection history.
+    /// The hashes only rule states out quickly; matching hashes are
+    /// confirmed by comparing the states themselves.
+    fn find_repeated_state(&self) -> Option<u32> {
+        let (hash, _, current) = self.history.back()?;
+        self.history
+            .iter()
+            .rev()
+            .skip(1)
+            .find(|(previous_hash, _, previous)| previous_hash == hash && previous == current)
+            .map(|&(_, time_step, _)| time_step)
+    }
+
+    fn check_outcome(&mut self) -> StepOutcome {
+        if self.map.current == self.map.previous {
+            return StepOutcome::ReachedSteadyState;
+        }
+
+        self.remember_state();
+        if let Some(start) = self.find_repeated_state() {
+            StepOutcome::ReachedCycle {
+                period: self.time_step - start,
+                start,
+            }
+        } else if self.time_step >= self.config.time_limit {
+            StepOutcome::ReachedTimeLimit
+        } else {
+            StepOutcome::Continued
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zone::ZoneRegistry;
+
+    fn simulation(cycle_detection_window: u32) -> Simulation {
+        let mut config = Config::new("region.csv", 100, 1);
+        config.cycle_detection_window = cycle_detection_window;
+        let map = Map::parse("region.csv", "P,T,R", &ZoneRegistry::default()).unwrap();
+
+        Simulation::new(config, map)
+    }
+
+    /// Moves to the next time step with the residential zone at
+    /// `population`, as if the region had grown there.
+    fn visit(simulation: &mut Simulation, population: u32) -> StepOutcome {
+        simulation.map.update_previous();
+        simulation.map.current.cells_mut()[2].population = population;
+        simulation.time_step += 1;
+        simulation.check_outcome()
+    }
+
+    #[test]
+    fn detects_a_cycle_as_long_as_the_window() {
+        let mut simulation = simulation(2);
+
+        assert_eq!(visit(&mut simulation, 1), StepOutcome::Continued);
+        assert_eq!(
+            visit(&mut simulation, 0),
+            StepOutcome::ReachedCycle {
+                period: 2,
+                start: 0
+            }
+        );
+    }
+
+    #[test]
+    fn ignores_cycles_longer_than_the_window() {
+        let mut simulation = simulation(2);
+
+        assert_eq!(visit(&mut simulation, 1), StepOutcome::Continued);
+        assert_eq!(visit(&mut simulation, 2), StepOutcome::Continued);
+        assert_eq!(visit(&mut simulation, 0), StepOutcome::Continued);
+        assert_eq!(
+            visit(&mut simulation, 2),
+            StepOutcome::ReachedCycle {
+                period: 2,
+                start: 2
+            }
         );
-        println!(
-            "Available Goods: {}\n",
-            Map::get_available_goods(&self.map.current)
-        )
     }
 
-    fn should_simulation_end(&self) -> bool {
-        self.time_step >= self.config.time_limit
-            || self.map.current == *self.map.previous.as_ref().unwrap()
+    #[test]
+    fn window_of_one_only_remembers_the_previous_state() {
+        let mut simulation = simulation(1);
+
+        assert_eq!(visit(&mut simulation, 1), StepOutcome::Continued);
+        assert_eq!(visit(&mut simulation, 0), StepOutcome::Continued);
+        let remembered: Vec<u32> = simulation
+            .history
+            .iter()
+            .map(|&(_, time_step, _)| time_step)
+            .collect();
+        assert_eq!(remembered, [1, 2]);
+    }
+
+    #[test]
+    fn window_of_zero_only_detects_steady_states() {
+        let mut simulation = simulation(0);
+
+        assert_eq!(visit(&mut simulation, 1), StepOutcome::Continued);
+        assert_eq!(visit(&mut simulation, 0), StepOutcome::Continued);
+        assert_eq!(visit(&mut simulation, 1), StepOutcome::Continued);
+        assert!(simulation.history.is_empty());
+        assert_eq!(visit(&mut simulation, 1), StepOutcome::ReachedSteadyState);
+    }
+
+    #[test]
+    fn confirms_matching_hashes_with_the_states() {
+        let mut simulation = simulation(4);
+        visit(&mut simulation, 1);
+
+        // Pretend the current state collides with the initial one.
+        let (hash, ..) = *simulation.history.back().unwrap();
+        simulation.history[0].0 = hash;
+
+        assert_eq!(simulation.find_repeated_state(), None);
     }
 }
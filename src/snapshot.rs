@@ -0,0 +1,457 @@
+use crate::{
+    config::{Config, Setting},
+    error::{Error, Result},
+    map::Map,
+    simulation::Simulation,
+    zone::ZoneRegistry,
+};
+use std::{fmt, fs};
+
+/// The sections following the settings of a snapshot file, in order. Every
+/// section holds one comma separated line per row of the region.
+const SECTIONS: [&str; 6] = [
+    "Region",
+    "Population",
+    "Pollution",
+    "Distress",
+    "Empty",
+    "Abandoned",
+];
+
+/// The state of an in-progress simulation, which can be saved to a file and
+/// resumed later.
+///
+/// A snapshot file starts with the time step, the refresh state counter, the
+/// last time step that changed the region and the config in the config file
+/// format, followed by the region layout and the population, pollution,
+/// distress, time spent empty and abandonment of every cell. Power is traced
+/// again when the simulation is resumed, and cycle detection starts over.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub config: Config,
+    pub time_step: u32,
+    pub state: u32,
+    /// The last time step that changed the region.
+    pub last_change: u32,
+    /// Where the snapshot was read from, used in error messages.
+    filename: String,
+    /// Line of the `Region:` header, to point layout errors at the right line.
+    region_line: usize,
+    /// The region layout, in the region layout file format.
+    region: String,
+    cells: Vec<CellState>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CellState {
+    population: u32,
+    pollution: u32,
+    distress: u32,
+    empty_for: u32,
+    is_abandoned: bool,
+}
+
+impl CellState {
+    /// The value stored in a section of the snapshot file.
+    fn value(&self, section: &str) -> u32 {
+        match section {
+            "Population" => self.population,
+            "Pollution" => self.pollution,
+            "Distress" => self.distress,
+            "Empty" => self.empty_for,
+            "Abandoned" => self.is_abandoned as u32,
+            _ => unreachable!("'{}' is not a cell section", section),
+        }
+    }
+}
+
+impl Snapshot {
+    pub fn new(simulation: &Simulation) -> Self {
+        let grid = &simulation.map().current;
+        let region = grid
+            .rows()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| cell.cell_type.as_char().to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let cells = grid
+            .cells()
+            .iter()
+            .map(|cell| CellState {
+                population: cell.population,
+                pollution: cell.pollution,
+                distress: cell.distress,
+                empty_for: cell.empty_for,
+                is_abandoned: cell.is_abandoned,
+            })
+            .collect();
+
+        Snapshot {
+            config: simulation.config().clone(),
+            time_step: simulation.time_step(),
+            state: simulation.state(),
+            last_change: simulation.last_change(),
+            filename: simulation.config().region_layout_filename.clone(),
+            region_line: 0,
+            region,
+            cells,
+        }
+    }
+
+    pub fn from_file(snapshot_filename: &str) -> Result<Self> {
+        let contents = fs::read_to_string(snapshot_filename).map_err(|source| Error::Io {
+            filename: snapshot_filename.to_string(),
+            source,
+        })?;
+
+        Self::parse(snapshot_filename, &contents)
+    }
+
+    /// Parses a snapshot file. The settings at the top are read like a
+    /// config file, with the additional `Time Step`, `State` and
+    /// `Last Change` keys.
+    pub fn parse(snapshot_filename: &str, contents: &str) -> Result<Self> {
+        let lines: Vec<&str> = contents.lines().collect();
+        let is_header =
+            |line: &str, section: &str| line.trim_end().strip_suffix(':') == Some(section);
+        let missing_key = |key: &str| Error::MissingKey {
+            filename: snapshot_filename.to_string(),
+            key: key.to_string(),
+        };
+
+        // Every section runs until the header of the next one.
+        let mut starts = Vec::new();
+        let mut search_from = 0;
+        for section in SECTIONS {
+            let start = lines[search_from..]
+                .iter()
+                .position(|line| is_header(line, section))
+                .map(|offset| search_from + offset)
+                .ok_or_else(|| missing_key(section))?;
+            starts.push(start);
+            search_from = start + 1;
+        }
+        starts.push(lines.len());
+
+        let settings = lines[..starts[0]].join("\n");
+        let config = Config::parse(snapshot_filename, &settings)?;
+        let mut time_step = None;
+        let mut state = None;
+        let mut last_change = None;
+        for setting in Setting::parse_all(snapshot_filename, &settings) {
+            let setting = setting?;
+            match setting.key {
+                "Time Step" => time_step = Some(setting.whole()?),
+                "State" => state = Some(setting.whole()?),
+                "Last Change" => last_change = Some(setting.whole()?),
+                _ => {}
+            }
+        }
+
+        let region_rows = &lines[starts[0] + 1..starts[1]];
+        let width = |row: &str| row.split(',').count();
+
+        let mut grids = Vec::new();
+        for (index, section) in SECTIONS.iter().enumerate().skip(1) {
+            let start = starts[index];
+            let rows = &lines[start + 1..starts[index + 1]];
+            let syntax_error = |line: usize, message: String| Error::Syntax {
+                filename: snapshot_filename.to_string(),
+                line: line + 1,
+                column: 1,
+                message,
+            };
+
+            if rows.len() != region_rows.len() {
+                return Err(syntax_error(
+                    start,
+                    format!(
+                        "Expected {} rows of {}, like the region, found {}",
+                        region_rows.len(),
+                        section,
+                        rows.len()
+                    ),
+                ));
+            }
+
+            let mut values = Vec::new();
+            for (y, (row, region_row)) in rows.iter().zip(region_rows).enumerate() {
+                let line = start + 1 + y;
+                if width(row) != width(region_row) {
+                    return Err(syntax_error(
+                        line,
+                        format!(
+                            "Expected {} values like the region row, found {}",
+                            width(region_row),
+                            width(row)
+                        ),
+                    ));
+                }
+
+                for value in row.split(',') {
+                    let value = value.trim().parse::<u32>().map_err(|_| {
+                        syntax_error(
+                            line,
+                            format!("Expected a whole number, found \"{}\"", value.trim()),
+                        )
+                    })?;
+                    values.push(value);
+                }
+            }
+            grids.push(values);
+        }
+
+        let cells = (0..grids[0].len())
+            .map(|index| CellState {
+                population: grids[0][index],
+                pollution: grids[1][index],
+                distress: grids[2][index],
+                empty_for: grids[3][index],
+                is_abandoned: grids[4][index] != 0,
+            })
+            .collect();
+
+        Ok(Snapshot {
+            config,
+            time_step: time_step.ok_or_else(|| missing_key("Time Step"))?,
+            state: state.ok_or_else(|| missing_key("State"))?,
+            last_change: last_change.ok_or_else(|| missing_key("Last Change"))?,
+            filename: snapshot_filename.to_string(),
+            region_line: starts[0] + 1,
+            region: region_rows.join("\n"),
+            cells,
+        })
+    }
+
+    pub fn save(&self, snapshot_filename: &str) -> Result<()> {
+        fs::write(snapshot_filename, self.to_string()).map_err(|source| Error::Io {
+            filename: snapshot_filename.to_string(),
+            source,
+        })
+    }
+
+    /// Rebuilds the region from the snapshot, with the zones registered in
+    /// `config.zones`, and continues the simulation where it was saved.
+    ///
+    /// Snapshots only record the built-in zones and the growth rules file, so
+    /// regions with custom zones have to be resumed with
+    /// [`Snapshot::resume_with`].
+    pub fn resume(self) -> Result<Simulation> {
+        let mut map =
+            Map::parse(&self.filename, &self.region, &self.config.zones).map_err(|error| {
+                match error {
+                    Error::InvalidLayout {
+                        filename,
+                        mut issues,
+                    } => {
+                        for issue in &mut issues {
+                            issue.line += self.region_line;
+                        }
+                        Error::InvalidLayout { filename, issues }
+                    }
+                    error => error,
+                }
+            })?;
+
+        for (cell, state) in map.current.cells_mut().iter_mut().zip(&self.cells) {
+            cell.population = state.population;
+            cell.pollution = state.pollution;
+            cell.distress = state.distress;
+            cell.empty_for = state.empty_for;
+            cell.is_abandoned = state.is_abandoned;
+        }
+        map.update_previous();
+
+        Ok(Simulation::resume(
+            self.config,
+            map,
+            self.time_step,
+            self.state,
+            self.last_change,
+        ))
+    }
+
+    /// Like [`Snapshot::resume`], with the zones of `zones` in place of the
+    /// ones loaded from the saved config.
+    pub fn resume_with(mut self, zones: &ZoneRegistry) -> Result<Simulation> {
+        self.config.zones = zones.clone();
+        self.resume()
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# Simulation snapshot")?;
+        writeln!(f, "Time Step:{}", self.time_step)?;
+        writeln!(f, "State:{}", self.state)?;
+        writeln!(f, "Last Change:{}", self.last_change)?;
+        write!(f, "{}", self.config)?;
+
+        writeln!(f, "Region:")?;
+        writeln!(f, "{}", self.region)?;
+
+        let width = self
+            .region
+            .lines()
+            .next()
+            .map_or(0, |row| row.split(',').count());
+        for section in &SECTIONS[1..] {
+            writeln!(f, "{}:", section)?;
+            for row in self.cells.chunks(width.max(1)) {
+                let row: Vec<String> = row
+                    .iter()
+                    .map(|cell| cell.value(section).to_string())
+                    .collect();
+                writeln!(f, "{}", row.join(","))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rules::GrowthRules, simulation::StepOutcome, zone::RuleZone};
+
+    const LAYOUT: &str = "P,T,R\n-,T,C\nR,T,I";
+
+    fn config() -> Config {
+        let mut config = Config::new("region.csv", 10, 2);
+        config.decline_delay = Some(2);
+        config.abandonment_delay = Some(1);
+        config
+    }
+
+    /// A simulation resumed mid-run, with zones in every stage of decline.
+    fn mid_run(config: Config) -> Simulation {
+        let mut map = Map::parse("region.csv", LAYOUT, &ZoneRegistry::default()).unwrap();
+        let cells = map.current.cells_mut();
+        cells[2].population = 2;
+        cells[2].pollution = 1;
+        cells[5].population = 1;
+        cells[5].distress = 2;
+        cells[6].distress = 2;
+        cells[6].empty_for = 1;
+        cells[6].is_abandoned = true;
+        cells[8].population = 1;
+        cells[8].pollution = 3;
+        map.update_previous();
+
+        Simulation::resume(config, map, 5, 2, 5)
+    }
+
+    #[test]
+    fn round_trips_mid_run_state() {
+        let simulation = mid_run(config());
+        let saved = Snapshot::new(&simulation).to_string();
+
+        let snapshot = Snapshot::parse("snapshot.txt", &saved).unwrap();
+        assert_eq!(snapshot.to_string(), saved);
+        assert_eq!(
+            (snapshot.time_step, snapshot.state, snapshot.last_change),
+            (5, 2, 5)
+        );
+
+        let resumed = snapshot.resume().unwrap();
+        assert_eq!(resumed.time_step(), 5);
+        assert_eq!(resumed.state(), 2);
+        assert_eq!(resumed.last_change(), 5);
+        assert_eq!(resumed.config().decline_delay, Some(2));
+        assert_eq!(resumed.config().abandonment_delay, Some(1));
+        assert_eq!(resumed.map().current, simulation.map().current);
+    }
+
+    #[test]
+    fn resumes_custom_zones_with_their_registry() {
+        let mut zones = ZoneRegistry::default();
+        zones
+            .register(RuleZone {
+                symbol: 'K',
+                name: "Park".to_string(),
+                rules: GrowthRules::default().residential,
+                growth_priority: 3,
+                is_pollution_sensitive: false,
+            })
+            .unwrap();
+        let mut config = config();
+        config.zones = zones.clone();
+        let mut map = Map::parse("region.csv", "P,T,R\nK,T,C", &zones).unwrap();
+        map.current.cells_mut()[3].population = 2;
+        map.update_previous();
+        let simulation = Simulation::resume(config, map, 5, 2, 5);
+        let saved = Snapshot::new(&simulation).to_string();
+
+        let snapshot = Snapshot::parse("snapshot.txt", &saved).unwrap();
+        assert!(matches!(
+            snapshot.clone().resume(),
+            Err(Error::InvalidLayout { .. })
+        ));
+
+        let resumed = snapshot.resume_with(&zones).unwrap();
+        assert_eq!(resumed.map().current, simulation.map().current);
+        assert!(resumed.map().zones.is_zone('K'));
+    }
+
+    #[test]
+    fn does_not_step_past_the_time_limit() {
+        let mut config = config();
+        config.time_limit = 5;
+        let saved = Snapshot::new(&mid_run(config)).to_string();
+
+        let mut resumed = Snapshot::parse("snapshot.txt", &saved)
+            .unwrap()
+            .resume()
+            .unwrap();
+        let before = resumed.map().current.clone();
+
+        assert_eq!(resumed.outcome(), StepOutcome::ReachedTimeLimit);
+        assert_eq!(resumed.step(), StepOutcome::ReachedTimeLimit);
+        assert_eq!(resumed.time_step(), 5);
+        assert_eq!(resumed.map().current, before);
+    }
+
+    #[test]
+    fn does_not_step_a_region_that_stopped_changing() {
+        let mut map = Map::parse("region.csv", LAYOUT, &ZoneRegistry::default()).unwrap();
+        map.update_previous();
+        let mut simulation = Simulation::resume(config(), map, 6, 3, 4);
+
+        assert_eq!(simulation.outcome(), StepOutcome::ReachedSteadyState);
+        assert_eq!(simulation.step(), StepOutcome::ReachedSteadyState);
+        assert_eq!(simulation.time_step(), 6);
+    }
+
+    #[test]
+    fn reports_invalid_cell_values_with_their_line() {
+        let saved = Snapshot::new(&mid_run(config())).to_string();
+        let broken = saved.replacen("Pollution:\n0,0,1", "Pollution:\n0,x,1", 1);
+        let line = broken
+            .lines()
+            .position(|line| line == "0,x,1")
+            .map(|index| index + 1);
+
+        match Snapshot::parse("snapshot.txt", &broken) {
+            Err(Error::Syntax {
+                line: error_line, ..
+            }) => assert_eq!(Some(error_line), line),
+            other => panic!("expected a syntax error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn requires_every_section() {
+        let saved = Snapshot::new(&mid_run(config())).to_string();
+        let missing = saved.replace("Empty:", "Vacant:");
+
+        assert!(matches!(
+            Snapshot::parse("snapshot.txt", &missing),
+            Err(Error::MissingKey { key, .. }) if key == "Empty"
+        ));
+    }
+}
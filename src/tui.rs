@@ -0,0 +1,327 @@
+use simcity::{Layer, Map, MapCell, MapView, Simulation};
+use std::{
+    io::{self, IsTerminal, Read, Write},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Time between two time steps while playing.
+const PLAY_INTERVAL: Duration = Duration::from_millis(250);
+/// Cells the cursor jumps with the shifted movement keys.
+const JUMP: usize = 10;
+
+const HELP: &str =
+    "space play/pause  n step  arrows/hjkl move  HJKL move 10  o layer  r redraw  q quit";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Char(char),
+    Quit,
+}
+
+/// Runs the full-screen interactive mode until the user quits. The
+/// simulation is left at the state it was quit at.
+pub fn run(simulation: &mut Simulation, layers: &[Layer], color: bool) -> io::Result<()> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err(io::Error::other("the interactive mode needs a terminal"));
+    }
+
+    let layer = Layer::ALL
+        .iter()
+        .position(|layer| Some(layer) == layers.first())
+        .unwrap_or(0);
+    let _terminal = Terminal::enter()?;
+    let mut app = App {
+        simulation,
+        size: Terminal::size(),
+        cursor: (0, 0),
+        offset: (0, 0),
+        playing: false,
+        layer,
+        color,
+    };
+    let keys = read_keys();
+    let mut next_step = Instant::now() + PLAY_INTERVAL;
+
+    loop {
+        app.draw()?;
+
+        let key = if app.playing {
+            keys.recv_timeout(next_step.saturating_duration_since(Instant::now()))
+        } else {
+            keys.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        };
+        match key {
+            Ok(Key::Quit) | Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            Ok(key) => {
+                app.handle(key);
+                next_step = Instant::now() + PLAY_INTERVAL;
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                app.step();
+                next_step += PLAY_INTERVAL;
+            }
+        }
+    }
+}
+
+struct App<'a> {
+    simulation: &'a mut Simulation,
+    /// The rows and columns of the terminal, queried again by the redraw
+    /// key.
+    size: (usize, usize),
+    /// The selected cell.
+    cursor: (usize, usize),
+    /// The top-left cell shown on screen.
+    offset: (usize, usize),
+    playing: bool,
+    /// The shown layer, as an index into `Layer::ALL`.
+    layer: usize,
+    color: bool,
+}
+
+impl App<'_> {
+    fn handle(&mut self, key: Key) {
+        let grid = &self.simulation.map().current;
+        let (max_x, max_y) = (grid.width() - 1, grid.height() - 1);
+        let (x, y) = self.cursor;
+
+        match key {
+            Key::Up | Key::Char('k') => self.cursor.1 = y.saturating_sub(1),
+            Key::Down | Key::Char('j') => self.cursor.1 = (y + 1).min(max_y),
+            Key::Left | Key::Char('h') => self.cursor.0 = x.saturating_sub(1),
+            Key::Right | Key::Char('l') => self.cursor.0 = (x + 1).min(max_x),
+            Key::Char('K') => self.cursor.1 = y.saturating_sub(JUMP),
+            Key::Char('J') => self.cursor.1 = (y + JUMP).min(max_y),
+            Key::Char('H') => self.cursor.0 = x.saturating_sub(JUMP),
+            Key::Char('L') => self.cursor.0 = (x + JUMP).min(max_x),
+            Key::Char(' ') => {
+                self.playing = !self.playing && !self.simulation.outcome().is_finished()
+            }
+            Key::Char('n') => {
+                self.playing = false;
+                self.step();
+            }
+            Key::Char('o') => self.layer = (self.layer + 1) % Layer::ALL.len(),
+            // Ctrl-L, the usual redraw key, or `r` after resizing the
+            // terminal.
+            Key::Char('r' | '\x0c') => self.size = Terminal::size(),
+            _ => {}
+        }
+    }
+
+    fn step(&mut self) {
+        if self.simulation.step().is_finished() {
+            self.playing = false;
+        }
+    }
+
+    fn draw(&mut self) -> io::Result<()> {
+        let (rows, columns) = self.size;
+        let map = self.simulation.map();
+        let grid = &map.current;
+        let layer = Layer::ALL[self.layer];
+
+        // Header, two borders, two lines of cell details and the help line.
+        let visible_rows = rows.saturating_sub(6).max(1).min(grid.height());
+        let visible_columns = (columns.saturating_sub(2) / 4).max(1).min(grid.width());
+        self.offset.0 = scroll(self.offset.0, self.cursor.0, visible_columns);
+        self.offset.1 = scroll(self.offset.1, self.cursor.1, visible_rows);
+
+        let status = if self.simulation.outcome().is_finished() {
+            self.simulation.outcome().to_string()
+        } else if self.playing {
+            "Playing".to_string()
+        } else {
+            "Paused".to_string()
+        };
+        let mut lines = vec![format!(
+            "Time Step: {}  Layer: {}  {}",
+            self.simulation.time_step(),
+            layer.title(),
+            status
+        )];
+
+        let view = MapView::new(map).with_layer(layer).with_color(self.color);
+        let border = "-".repeat(visible_columns * 4 + 2);
+        lines.push(border.clone());
+        for row in grid.rows().skip(self.offset.1).take(visible_rows) {
+            let mut line = String::from("|");
+            for cell in row.iter().skip(self.offset.0).take(visible_columns) {
+                let (x, y) = cell.position;
+                if (x as usize, y as usize) == self.cursor {
+                    line.push_str(&format!(" \x1b[7m{}\x1b[0m", view.cell_text(cell)));
+                } else {
+                    line.push_str(&format!(" {}", view.cell_text(cell)));
+                }
+            }
+            line.push('|');
+            lines.push(line);
+        }
+        lines.push(border);
+
+        let (x, y) = self.cursor;
+        let cell = grid.get(x, y).expect("cursor stays inside the region");
+        lines.extend(cell_details(map, cell));
+        lines.push(HELP.to_string());
+
+        let mut stdout = io::stdout().lock();
+        write!(stdout, "\x1b[H")?;
+        for line in lines {
+            write!(stdout, "{}\x1b[K\r\n", line)?;
+        }
+        write!(stdout, "\x1b[J")?;
+        stdout.flush()
+    }
+}
+
+/// Moves the first visible index so that `cursor` stays within `visible`
+/// cells.
+fn scroll(offset: usize, cursor: usize, visible: usize) -> usize {
+    if cursor < offset {
+        cursor
+    } else if cursor >= offset + visible {
+        cursor + 1 - visible
+    } else {
+        offset
+    }
+}
+
+fn cell_details(map: &Map, cell: &MapCell) -> [String; 2] {
+    let yes_no = |value: bool| if value { "yes" } else { "no" };
+    let kind = match map.zones.for_cell(cell) {
+        Some(zone) => zone.name().to_string(),
+        None => match cell.cell_type.as_char() {
+            'T' => "Powerline".to_string(),
+            '#' => "Powerline over road".to_string(),
+            '-' => "Road".to_string(),
+            'P' => "Power plant".to_string(),
+            ' ' => "Empty".to_string(),
+            symbol => format!("'{}'", symbol),
+        },
+    };
+
+    [
+        format!(
+            "({}, {}) {}  Population: {}  Pollution: {}  Neighbor population: {}",
+            cell.position.0,
+            cell.position.1,
+            kind,
+            cell.population,
+            cell.pollution,
+            cell.count_adjacent_population(&map.current)
+        ),
+        format!(
+            "Powered: {}  Browned out: {}  Road access: {}  Land value: {}",
+            yes_no(cell.is_powered),
+            yes_no(cell.is_browned_out),
+            yes_no(cell.is_road_adjacent),
+            Map::land_value(&map.current, cell)
+        ),
+    ]
+}
+
+/// Reads key presses on a separate thread, so the simulation can keep
+/// playing while waiting for input.
+fn read_keys() -> Receiver<Key> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut stdin = io::stdin().lock();
+        let mut buffer = [0; 64];
+        while let Ok(length @ 1..) = stdin.read(&mut buffer) {
+            for key in parse_keys(&buffer[..length]) {
+                if sender.send(key).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    receiver
+}
+
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut bytes = bytes.iter().copied();
+
+    while let Some(byte) = bytes.next() {
+        let key = match byte {
+            // Arrow keys arrive as ESC [ A to ESC [ D.
+            0x1b => match (bytes.next(), bytes.next()) {
+                (Some(b'['), Some(b'A')) => Key::Up,
+                (Some(b'['), Some(b'B')) => Key::Down,
+                (Some(b'['), Some(b'C')) => Key::Right,
+                (Some(b'['), Some(b'D')) => Key::Left,
+                (None, _) => Key::Quit,
+                _ => continue,
+            },
+            // Ctrl-C, since raw mode turns off signals.
+            0x03 | b'q' => Key::Quit,
+            byte => Key::Char(byte as char),
+        };
+        keys.push(key);
+    }
+
+    keys
+}
+
+/// Puts the terminal into raw mode on the alternate screen, and restores it
+/// when dropped.
+struct Terminal {
+    /// The terminal settings to restore, as printed by `stty -g`.
+    settings: String,
+}
+
+impl Terminal {
+    fn enter() -> io::Result<Self> {
+        let settings = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+
+        let mut stdout = io::stdout().lock();
+        write!(stdout, "\x1b[?1049h\x1b[?25l")?;
+        stdout.flush()?;
+
+        Ok(Terminal { settings })
+    }
+
+    /// The number of rows and columns of the terminal.
+    fn size() -> (usize, usize) {
+        stty(&["size"])
+            .ok()
+            .and_then(|size| {
+                let (rows, columns) = size.split_once(' ')?;
+                Some((rows.parse().ok()?, columns.parse().ok()?))
+            })
+            .unwrap_or((24, 80))
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout().lock();
+        let _ = write!(stdout, "\x1b[?25h\x1b[?1049l");
+        let _ = stdout.flush();
+        let _ = stty(&[&self.settings]);
+    }
+}
+
+/// Runs `stty` on the terminal connected to stdin and returns its output.
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed"));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
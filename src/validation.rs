@@ -0,0 +1,242 @@
+use crate::zone::ZoneRegistry;
+use std::fmt;
+
+/// Symbols of the cells that are not zones: powerlines (`T`), power plants
+/// (`P`), roads (`-`), powerlines over roads (`#`) and empty land (` `).
+/// Every other symbol in a layout has to be a registered zone.
+pub const INFRASTRUCTURE_SYMBOLS: [char; 5] = ['T', '#', '-', 'P', ' '];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The layout cannot be simulated.
+    Error,
+    /// The layout can be simulated, but part of it will never develop fully.
+    Warning,
+}
+
+/// A single problem found in a region layout. Line and column numbers start
+/// at 1 and point at the cell's symbol in the layout file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutIssue {
+    pub severity: Severity,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LayoutIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.line, self.column, severity, self.message
+        )
+    }
+}
+
+/// A layout split into cell symbols, together with every problem found in it.
+/// Fields that are not a single symbol are stored as `None`.
+pub struct LayoutCheck {
+    pub symbols: Vec<Vec<Option<char>>>,
+    pub issues: Vec<LayoutIssue>,
+}
+
+impl LayoutCheck {
+    pub fn has_errors(&self) -> bool {
+        self.issues
+            .iter()
+            .any(|issue| issue.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> Vec<LayoutIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Warning)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Checks the whole layout before it is turned into a map, collecting all
+/// problems instead of stopping at the first one. Zone symbols are looked up
+/// in `zones`.
+pub fn validate_layout(contents: &str, zones: &ZoneRegistry) -> LayoutCheck {
+    let mut issues = Vec::new();
+    let mut symbols = Vec::new();
+    let mut columns = Vec::new();
+
+    for (y, line) in contents.lines().enumerate() {
+        let mut row = Vec::new();
+        let mut row_columns = Vec::new();
+        let mut column = 1;
+
+        for (x, field) in line.split(',').enumerate() {
+            let mut chars = field.chars();
+            let symbol = match (chars.next(), chars.next()) {
+                (Some(symbol), None)
+                    if INFRASTRUCTURE_SYMBOLS.contains(&symbol) || zones.is_zone(symbol) =>
+                {
+                    Some(symbol)
+                }
+                (Some(symbol), None) => {
+                    issues.push(error(
+                        y,
+                        column,
+                        format!("Unknown cell symbol '{}' at ({}, {})", symbol, x, y),
+                    ));
+                    None
+                }
+                _ => {
+                    issues.push(error(
+                        y,
+                        column,
+                        format!(
+                            "Expected a single cell symbol at ({}, {}), found \"{}\"",
+                            x, y, field
+                        ),
+                    ));
+                    None
+                }
+            };
+
+            row.push(symbol);
+            row_columns.push(column);
+            column += field.chars().count() + 1;
+        }
+
+        symbols.push(row);
+        columns.push(row_columns);
+    }
+
+    if symbols.is_empty() {
+        issues.push(error(0, 1, "The region layout is empty".to_string()));
+        return LayoutCheck { symbols, issues };
+    }
+
+    let width = symbols[0].len();
+    for (y, row) in symbols.iter().enumerate().skip(1) {
+        if row.len() != width {
+            issues.push(error(
+                y,
+                1,
+                format!(
+                    "Row {} has {} cells, expected {} like the first row",
+                    y,
+                    row.len(),
+                    width
+                ),
+            ));
+        }
+    }
+
+    if !symbols.iter().flatten().any(|symbol| *symbol == Some('P')) {
+        issues.push(error(
+            0,
+            1,
+            "The region has no power plant ('P')".to_string(),
+        ));
+    }
+
+    // Zones only need power to start growing, after that growth spreads
+    // through neighboring zones. A block of connected zones is therefore
+    // only unreachable when none of its cells touches the power grid.
+    let energized = energized_cells(&symbols);
+    let mut visited: Vec<Vec<bool>> = symbols.iter().map(|row| vec![false; row.len()]).collect();
+    for y in 0..symbols.len() {
+        for x in 0..symbols[y].len() {
+            if visited[y][x] || !is_zone(symbols[y][x], zones) {
+                continue;
+            }
+
+            let mut block_size = 0;
+            let mut powered = false;
+            let mut stack = vec![(x, y)];
+            visited[y][x] = true;
+
+            while let Some((cx, cy)) = stack.pop() {
+                block_size += 1;
+                for (nx, ny) in neighbors(&symbols, cx, cy) {
+                    powered |= energized[ny][nx];
+                    if !visited[ny][nx] && is_zone(symbols[ny][nx], zones) {
+                        visited[ny][nx] = true;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+
+            if !powered {
+                issues.push(LayoutIssue {
+                    severity: Severity::Warning,
+                    line: y + 1,
+                    column: columns[y][x],
+                    message: format!(
+                        "The block of {} zone cell(s) starting at ({}, {}) can never be powered, no connected powerline or power plant is adjacent",
+                        block_size, x, y
+                    ),
+                });
+            }
+        }
+    }
+
+    LayoutCheck { symbols, issues }
+}
+
+fn is_zone(symbol: Option<char>, zones: &ZoneRegistry) -> bool {
+    symbol.is_some_and(|symbol| zones.is_zone(symbol))
+}
+
+fn error(y: usize, column: usize, message: String) -> LayoutIssue {
+    LayoutIssue {
+        severity: Severity::Error,
+        line: y + 1,
+        column,
+        message,
+    }
+}
+
+/// Marks the power plants and every powerline connected to one of them.
+fn energized_cells(symbols: &[Vec<Option<char>>]) -> Vec<Vec<bool>> {
+    let mut energized: Vec<Vec<bool>> = symbols.iter().map(|row| vec![false; row.len()]).collect();
+    let mut stack: Vec<(usize, usize)> = Vec::new();
+
+    for (y, row) in symbols.iter().enumerate() {
+        for (x, symbol) in row.iter().enumerate() {
+            if *symbol == Some('P') {
+                energized[y][x] = true;
+                stack.push((x, y));
+            }
+        }
+    }
+
+    while let Some((x, y)) = stack.pop() {
+        for (nx, ny) in neighbors(symbols, x, y) {
+            if !energized[ny][nx] && matches!(symbols[ny][nx], Some('T' | '#' | 'P')) {
+                energized[ny][nx] = true;
+                stack.push((nx, ny));
+            }
+        }
+    }
+
+    energized
+}
+
+/// The up to 8 cells surrounding `(x, y)`, allowing for rows of different
+/// lengths.
+fn neighbors(
+    symbols: &[Vec<Option<char>>],
+    x: usize,
+    y: usize,
+) -> impl Iterator<Item = (usize, usize)> + '_ {
+    (-1..=1isize)
+        .flat_map(move |dy| (-1..=1isize).map(move |dx| (dx, dy)))
+        .filter(|&(dx, dy)| dx != 0 || dy != 0)
+        .filter_map(move |(dx, dy)| {
+            let nx = x.checked_add_signed(dx)?;
+            let ny = y.checked_add_signed(dy)?;
+            (ny < symbols.len() && nx < symbols[ny].len()).then_some((nx, ny))
+        })
+}
@@ -0,0 +1,344 @@
+use crate::{
+    error::{Error, Result},
+    map_cell::{CellType, MapCell},
+    map_grid::MapGrid,
+    rules::{GrowthRules, ZoneRules},
+    validation::INFRASTRUCTURE_SYMBOLS,
+};
+use std::{fmt, sync::Arc};
+
+/// How a kind of zone behaves. Every zone symbol in a region layout maps to
+/// an implementation through the [`ZoneRegistry`], so new kinds of zones can
+/// be added without touching the simulation itself.
+pub trait ZoneBehavior: fmt::Debug {
+    /// The symbol of the zone in region layouts.
+    fn symbol(&self) -> char;
+
+    /// The name of the zone in reports, e.g. `"Residential"`.
+    fn name(&self) -> &str;
+
+    /// Grows the zone based on its `previous` state, where `grid` is the
+    /// previous state of the whole region, using the available `workers`
+    /// and `goods` of its district. Returns the workers and goods left.
+    fn grow(
+        &self,
+        cell: &mut MapCell,
+        previous: &MapCell,
+        grid: &MapGrid,
+        workers: i32,
+        goods: i32,
+    ) -> (i32, i32);
+
+    /// Workers and goods the zone adds to (positive) or takes from
+    /// (negative) its district.
+    fn resources(&self, cell: &MapCell) -> (i32, i32);
+
+    /// Workers and goods the zone needs to keep running. A zone whose
+    /// district runs short of either is in distress.
+    fn needs(&self) -> (bool, bool) {
+        (false, false)
+    }
+
+    /// Pollution the zone gives off at its own position.
+    fn pollution_emission(&self, _cell: &MapCell) -> u32 {
+        0
+    }
+
+    /// Whether the configured pollution limits apply to the zone.
+    fn is_pollution_sensitive(&self) -> bool {
+        false
+    }
+
+    /// Zones with a lower priority grow first within a time step.
+    fn growth_priority(&self) -> u8 {
+        u8::MAX
+    }
+
+    /// What the zone looks like on the printed map.
+    fn glyph(&self, cell: &MapCell) -> String {
+        cell.to_string()
+    }
+}
+
+/// A zone that grows by a table of [`ZoneRules`]. Residential, commercial
+/// and industrial zones are built this way.
+#[derive(Debug, Clone)]
+pub struct RuleZone {
+    pub symbol: char,
+    pub name: String,
+    pub rules: ZoneRules,
+    pub growth_priority: u8,
+    pub is_pollution_sensitive: bool,
+}
+
+impl ZoneBehavior for RuleZone {
+    fn symbol(&self) -> char {
+        self.symbol
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Grows the zone by one population if it has the workers and goods the
+    /// growth costs and either is powered and empty or meets the threshold
+    /// for its population.
+    fn grow(
+        &self,
+        cell: &mut MapCell,
+        previous: &MapCell,
+        grid: &MapGrid,
+        workers: i32,
+        goods: i32,
+    ) -> (i32, i32) {
+        let (worker_cost, goods_cost) = (self.rules.worker_cost(), self.rules.goods_cost());
+
+        // Early return if not enough resources
+        if (worker_cost > 0 && workers < worker_cost) || (goods_cost > 0 && goods < goods_cost) {
+            return (workers, goods);
+        }
+
+        let Some(threshold) = self.rules.threshold(previous.population) else {
+            return (workers, goods);
+        };
+
+        let grows = (previous.population == 0 && previous.is_powered)
+            || previous
+                .neighbors(grid)
+                .filter(|neighbor| neighbor.population >= threshold.neighbor_population)
+                .take(threshold.neighbors as usize)
+                .count()
+                >= threshold.neighbors as usize;
+
+        if !grows {
+            return (workers, goods);
+        }
+
+        cell.population = previous.population + 1;
+        (workers - worker_cost, goods - goods_cost)
+    }
+
+    fn resources(&self, cell: &MapCell) -> (i32, i32) {
+        (
+            self.rules.workers_per_population * cell.population as i32,
+            self.rules.goods_per_population * cell.population as i32,
+        )
+    }
+
+    fn needs(&self) -> (bool, bool) {
+        (self.rules.worker_cost() > 0, self.rules.goods_cost() > 0)
+    }
+
+    fn pollution_emission(&self, cell: &MapCell) -> u32 {
+        self.rules.pollution_per_population * cell.population
+    }
+
+    fn is_pollution_sensitive(&self) -> bool {
+        self.is_pollution_sensitive
+    }
+
+    fn growth_priority(&self) -> u8 {
+        self.growth_priority
+    }
+}
+
+/// Maps region layout symbols to the zones they stand for.
+#[derive(Debug, Clone)]
+pub struct ZoneRegistry {
+    /// Registered zones in registration order.
+    zones: Vec<Arc<dyn ZoneBehavior + Send + Sync>>,
+}
+
+impl Default for ZoneRegistry {
+    fn default() -> Self {
+        Self::new(&GrowthRules::default())
+    }
+}
+
+impl ZoneRegistry {
+    /// Creates a registry with the residential (`R`), commercial (`C`) and
+    /// industrial (`I`) zones, growing by `rules`. Commercial zones grow
+    /// before industrial zones, which grow before residential zones.
+    pub fn new(rules: &GrowthRules) -> Self {
+        let mut registry = ZoneRegistry { zones: Vec::new() };
+
+        registry.insert(RuleZone {
+            symbol: 'R',
+            name: "Residential".to_string(),
+            rules: rules.residential.clone(),
+            growth_priority: 2,
+            is_pollution_sensitive: true,
+        });
+        registry.insert(RuleZone {
+            symbol: 'C',
+            name: "Commercial".to_string(),
+            rules: rules.commercial.clone(),
+            growth_priority: 0,
+            is_pollution_sensitive: false,
+        });
+        registry.insert(RuleZone {
+            symbol: 'I',
+            name: "Industrial".to_string(),
+            rules: rules.industrial.clone(),
+            growth_priority: 1,
+            is_pollution_sensitive: false,
+        });
+
+        registry
+    }
+
+    /// Adds a zone, replacing any zone registered for the same symbol.
+    ///
+    /// Fails if the symbol is one of the infrastructure symbols, which
+    /// cannot be zones.
+    pub fn register(&mut self, zone: impl ZoneBehavior + Send + Sync + 'static) -> Result<()> {
+        if INFRASTRUCTURE_SYMBOLS.contains(&zone.symbol()) {
+            return Err(Error::ReservedSymbol {
+                symbol: zone.symbol(),
+            });
+        }
+
+        self.insert(zone);
+        Ok(())
+    }
+
+    /// Replaced zones keep their place in the registration order.
+    fn insert(&mut self, zone: impl ZoneBehavior + Send + Sync + 'static) {
+        let zone = Arc::new(zone);
+        match self
+            .zones
+            .iter_mut()
+            .find(|registered| registered.symbol() == zone.symbol())
+        {
+            Some(registered) => *registered = zone,
+            None => self.zones.push(zone),
+        }
+    }
+
+    pub fn get(&self, symbol: char) -> Option<&dyn ZoneBehavior> {
+        self.zones().find(|zone| zone.symbol() == symbol)
+    }
+
+    /// The zone a cell belongs to, or `None` for cells that are not zones.
+    pub fn for_cell(&self, cell: &MapCell) -> Option<&dyn ZoneBehavior> {
+        if cell.cell_type.is_zone() {
+            self.get(cell.cell_type.as_char())
+        } else {
+            None
+        }
+    }
+
+    /// What a cell looks like on the printed map: the glyph of its zone, or
+    /// its symbol for cells that are not zones.
+    pub fn glyph(&self, cell: &MapCell) -> String {
+        match self.for_cell(cell) {
+            Some(zone) => zone.glyph(cell),
+            None => cell.to_string(),
+        }
+    }
+
+    /// Every registered zone, in registration order: residential,
+    /// commercial and industrial first, then the custom zones.
+    pub fn zones(&self) -> impl Iterator<Item = &dyn ZoneBehavior> {
+        self.zones
+            .iter()
+            .map(|zone| zone.as_ref() as &dyn ZoneBehavior)
+    }
+
+    pub fn is_zone(&self, symbol: char) -> bool {
+        self.get(symbol).is_some()
+    }
+
+    /// The cell type a layout symbol stands for.
+    pub fn cell_type(&self, symbol: char) -> CellType {
+        match symbol {
+            'R' if self.is_zone('R') => CellType::Residential('R'),
+            'C' if self.is_zone('C') => CellType::Commercial('C'),
+            'I' if self.is_zone('I') => CellType::Industrial('I'),
+            _ if self.is_zone(symbol) => CellType::Custom(symbol),
+            _ => CellType::Other(symbol),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, map::Map, simulation::Simulation};
+
+    fn park(symbol: char) -> RuleZone {
+        RuleZone {
+            symbol,
+            name: "Park".to_string(),
+            rules: GrowthRules::default().residential,
+            growth_priority: 3,
+            is_pollution_sensitive: false,
+        }
+    }
+
+    #[test]
+    fn registers_custom_zones() {
+        let mut zones = ZoneRegistry::default();
+        zones.register(park('K')).unwrap();
+
+        assert_eq!(zones.get('K').map(|zone| zone.name()), Some("Park"));
+        assert_eq!(zones.cell_type('K'), CellType::Custom('K'));
+        assert_eq!(zones.cell_type('R'), CellType::Residential('R'));
+    }
+
+    #[test]
+    fn keeps_registration_order() {
+        let mut zones = ZoneRegistry::default();
+        zones.register(park('K')).unwrap();
+        zones.register(park('C')).unwrap();
+
+        let symbols: Vec<char> = zones.zones().map(|zone| zone.symbol()).collect();
+        assert_eq!(symbols, ['R', 'C', 'I', 'K']);
+        assert_eq!(zones.get('C').map(|zone| zone.name()), Some("Park"));
+    }
+
+    #[test]
+    fn area_stats_count_custom_zones() {
+        let mut zones = ZoneRegistry::default();
+        zones.register(park('K')).unwrap();
+        let mut map = Map::parse("region.csv", "P,T,R\nK,T,C", &zones).unwrap();
+        for (cell, population) in map.current.cells_mut().iter_mut().zip([0, 0, 1, 4, 0, 2]) {
+            cell.population = population;
+        }
+
+        let stats = Map::get_area_stats(&map.current, &zones, &map.area());
+        assert_eq!(
+            stats.populations,
+            [
+                ("Residential".to_string(), 1),
+                ("Commercial".to_string(), 2),
+                ("Industrial".to_string(), 0),
+                ("Park".to_string(), 4),
+            ]
+        );
+        assert_eq!(stats.population("Park"), Some(4));
+        assert_eq!(stats.total_population(), 7);
+    }
+
+    #[test]
+    fn rejects_infrastructure_symbols() {
+        let mut zones = ZoneRegistry::default();
+
+        for symbol in INFRASTRUCTURE_SYMBOLS {
+            assert!(matches!(
+                zones.register(park(symbol)),
+                Err(Error::ReservedSymbol { symbol: rejected }) if rejected == symbol
+            ));
+            assert!(!zones.is_zone(symbol));
+        }
+    }
+
+    #[test]
+    fn simulations_can_move_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ZoneRegistry>();
+        assert_send_sync::<Config>();
+        assert_send_sync::<Map>();
+        assert_send_sync::<Simulation>();
+    }
+}